@@ -5,9 +5,11 @@ extern crate test;
 #[cfg(test)]
 mod tests {
     use role_manager::analyzer::full_analysis;
+    use role_manager::analyzer::pipeline::DEFAULT_ANALYSIS_WORKERS;
     use test::Bencher;
     use chrono::Duration;
-    use sea_orm::{Database, DatabaseConnection, EntityTrait, ColumnTrait, QueryFilter};
+    use std::sync::Arc;
+    use sea_orm::{Database, DatabaseConnection};
     use serenity::all::GuildId;
     use serenity::model::guild::Member;
     use role_manager::config::load_config;
@@ -15,13 +17,36 @@ mod tests {
     use role_manager::boards::srcom::SrComBoardsState;
     use role_manager::analyzer::role_definition::RoleDefinition;
     use role_manager::model::lumadb::verified_connections;
+    use role_manager::store::ConnectionStore;
+    use role_manager::store::database::DatabaseStore;
 
-    #[bench]
-    fn analysis_bench(b: &mut Bencher) {
+    /// Everything an analysis needs, fetched once so only the analysis itself is measured
+    struct Setup {
+        runtime: tokio::runtime::Runtime,
+        definition: RoleDefinition,
+        connections: Vec<verified_connections::Model>,
+        user_ids: Vec<u64>,
+        srcom_state: SrComBoardsState,
+        cm_state: CmBoardsState
+    }
+
+    impl Setup {
+        fn analyze(&self, workers: usize) {
+            self.runtime.block_on(full_analysis(
+                self.definition.clone(),
+                self.connections.clone(),
+                self.user_ids.clone(),
+                self.srcom_state.clone(),
+                self.cm_state.clone(),
+                workers
+            )).unwrap();
+        }
+    }
+
+    fn setup() -> Setup {
         println!("Setting up runtime");
 
         let runtime = tokio::runtime::Runtime::new().unwrap();
-        let _guard = runtime.enter();
 
         // Definition file to use
         println!("Reading definition file");
@@ -30,19 +55,22 @@ mod tests {
         // Setup state for fetching info
         println!("Setting up state for run");
         let config = load_config();
-        let db: DatabaseConnection = runtime.block_on(Database::connect(&config.database_url)).expect(
-            format!("Failed to open connection to database at {}", &config.database_url).as_str()
+        let database_url = config.database_url.as_ref().expect("The analysis bench needs a database_url");
+        let analysis_guild = GuildId::new(config.analysis_guild.expect("The analysis bench needs an analysis_guild"));
+        let db: DatabaseConnection = runtime.block_on(Database::connect(database_url)).expect(
+            format!("Failed to open connection to database at {}", database_url).as_str()
         );
-        let srcom_state = SrComBoardsState::new(Duration::minutes(15));
-        let cm_state = CmBoardsState::new(Duration::minutes(15));
+        let store = DatabaseStore::new(Arc::new(db));
+        let (srcom_state, cm_state) = {
+            let _guard = runtime.enter();
+            (SrComBoardsState::new(Duration::minutes(15)), CmBoardsState::new(Duration::minutes(15)))
+        };
 
         let discord_http = serenity::http::Http::new(config.discord_bot_token.as_str());
 
         // Fetch database connections
         println!("Fetching database connections");
-        let connections: Vec<verified_connections::Model> = runtime.block_on(verified_connections::Entity::find()
-            .filter(verified_connections::Column::Removed.eq(0))
-            .all(&db)).unwrap();
+        let connections: Vec<verified_connections::Model> = runtime.block_on(store.connections()).unwrap();
 
         // Fetch discord users
         println!("Fetching discord users");
@@ -50,7 +78,7 @@ mod tests {
         let mut offset: Option<u64> = None;
 
         loop {
-            let iteration = runtime.block_on(discord_http.get_guild_members(GuildId::new(146404426746167296), Some(1_000), offset)).unwrap();
+            let iteration = runtime.block_on(discord_http.get_guild_members(analysis_guild, Some(1_000), offset)).unwrap();
             if !iteration.is_empty() {
                 offset = Some(iteration.get(iteration.len() - 1).unwrap().user.id.get());
             }
@@ -64,13 +92,31 @@ mod tests {
             }
         }
 
-        // Warm-up run
+        let user_ids: Vec<u64> = users.iter().map(|user| user.user.id.get()).collect();
+
+        let setup = Setup { runtime, definition, connections, user_ids, srcom_state, cm_state };
+
+        // Warm-up run, so boards are cached for every measured run
         println!("Warmup run");
-        let _ = runtime.block_on(full_analysis(definition.clone(), connections.clone(), users.clone(), srcom_state.clone(), cm_state.clone())).unwrap();
+        setup.analyze(DEFAULT_ANALYSIS_WORKERS);
+
+        setup
+    }
+
+    #[bench]
+    fn analysis_bench(b: &mut Bencher) {
+        let setup = setup();
+
+        println!("Go!!");
+        b.iter(|| setup.analyze(DEFAULT_ANALYSIS_WORKERS))
+    }
+
+    /// Baseline for `analysis_bench`, analyzing one member at a time
+    #[bench]
+    fn sequential_analysis_bench(b: &mut Bencher) {
+        let setup = setup();
 
         println!("Go!!");
-        b.iter(|| {
-            runtime.block_on(full_analysis(definition.clone(), connections.clone(), users.clone(), srcom_state.clone(), cm_state.clone())).unwrap();
-        })
+        b.iter(|| setup.analyze(1))
     }
 }
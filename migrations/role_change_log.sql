@@ -0,0 +1,16 @@
+-- Record of every badge role change applied by the bot, read by `/server history`
+CREATE TABLE IF NOT EXISTS role_change_log (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    server_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    badge VARCHAR(255) NOT NULL,
+    completed TINYINT NOT NULL,
+    direction VARCHAR(16) NOT NULL,
+    reason TEXT NOT NULL,
+    causes TEXT NOT NULL,
+    definition_version VARCHAR(64) NULL,
+    changed_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (id),
+    INDEX role_change_log_history (server_id, user_id, changed_at)
+);
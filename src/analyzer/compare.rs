@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::analyzer::role_definition::RoleDefinition;
+use crate::analyzer::user::{self, AnalyzedUser};
+use crate::boards::cm::CmBoardsState;
+use crate::boards::srcom::SrComBoardsState;
+use crate::error::RoleManagerError;
+use crate::model::lumadb::verified_connections;
+
+/// How much of a badge a user meets
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BadgeStatus {
+    Missing,
+    Partial,
+    Complete
+}
+
+impl Display for BadgeStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "none"),
+            Self::Partial => write!(f, "partial"),
+            Self::Complete => write!(f, "complete")
+        }
+    }
+}
+
+impl BadgeStatus {
+    fn of(analysis: &AnalyzedUser, badge_name: &str) -> Self {
+        match analysis.badges.iter().find(|badge| badge.definition.name == badge_name) {
+            Some(badge) if badge.is_complete() => Self::Complete,
+            Some(_) => Self::Partial,
+            None => Self::Missing
+        }
+    }
+}
+
+/// Who would gain, lose or keep a badge when switching definitions, by discord id
+#[derive(Debug, Default)]
+pub struct BadgeComparison {
+    pub badge: String,
+    pub gained: Vec<u64>,
+    pub lost: Vec<u64>,
+    pub kept: Vec<u64>
+}
+
+/// A user who meets a badge under at least one of the definitions
+#[derive(Debug)]
+pub struct UserBadgeComparison {
+    pub user_id: u64,
+    pub user_name: String,
+    pub badge: String,
+    pub old: BadgeStatus,
+    pub new: BadgeStatus
+}
+
+#[derive(Debug)]
+pub struct DefinitionComparison {
+    pub badges: Vec<BadgeComparison>,
+    pub users: Vec<UserBadgeComparison>
+}
+
+impl DefinitionComparison {
+    /// A sheet with a row for every user and badge they meet under either definition
+    pub fn to_csv(&self) -> Result<Vec<u8>, RoleManagerError> {
+        let mut report = csv::Writer::from_writer(vec![]);
+        report.write_record(["Discord User", "Badge", "Old", "New", "Change"])
+            .map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+
+        for user in &self.users {
+            let change = match (user.old, user.new) {
+                (BadgeStatus::Missing, _) => "gain",
+                (_, BadgeStatus::Missing) => "lose",
+                _ => "keep"
+            };
+
+            report.write_record([
+                user.user_name.as_str(),
+                user.badge.as_str(),
+                user.old.to_string().as_str(),
+                user.new.to_string().as_str(),
+                change
+            ]).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+        }
+
+        report.into_inner()
+            .map_err(|e| RoleManagerError::new(format!("Failed to generate report: {}", e)))
+    }
+}
+
+/// Evaluates two definitions over the same users, given as (discord id, name) pairs, and compares which badges
+/// each user would have. Badges are matched by name.
+pub async fn compare_definitions(old: &RoleDefinition,
+                                 new: &RoleDefinition,
+                                 users: &[(u64, String)],
+                                 connections: &[verified_connections::Model],
+                                 srcom_state: SrComBoardsState,
+                                 cm_state: CmBoardsState) -> Result<DefinitionComparison, RoleManagerError> {
+    // Badges of the old definition first, followed by the ones which only exist in the new one
+    let mut badges: Vec<BadgeComparison> = Vec::new();
+    for badge in old.badges.iter().chain(new.badges.iter()) {
+        if !badges.iter().any(|b| b.badge == badge.name) {
+            badges.push(BadgeComparison {
+                badge: badge.name.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut connections_by_user: HashMap<i64, Vec<verified_connections::Model>> = HashMap::new();
+    for connection in connections {
+        connections_by_user.entry(connection.user_id).or_default().push(connection.clone());
+    }
+
+    let mut user_comparisons = Vec::new();
+    for (user_id, user_name) in users {
+        let user_connections = match connections_by_user.get(&(*user_id as i64)) {
+            Some(user_connections) => user_connections,
+            None => continue
+        };
+
+        let old_analysis = user::analyze_user(*user_id, old, user_connections, srcom_state.clone(), cm_state.clone(), false).await?;
+        let new_analysis = user::analyze_user(*user_id, new, user_connections, srcom_state.clone(), cm_state.clone(), false).await?;
+
+        for comparison in badges.iter_mut() {
+            let old_status = BadgeStatus::of(&old_analysis, &comparison.badge);
+            let new_status = BadgeStatus::of(&new_analysis, &comparison.badge);
+
+            match (old_status, new_status) {
+                (BadgeStatus::Missing, BadgeStatus::Missing) => continue,
+                (BadgeStatus::Missing, _) => comparison.gained.push(*user_id),
+                (_, BadgeStatus::Missing) => comparison.lost.push(*user_id),
+                _ => comparison.kept.push(*user_id)
+            }
+
+            user_comparisons.push(UserBadgeComparison {
+                user_id: *user_id,
+                user_name: user_name.clone(),
+                badge: comparison.badge.clone(),
+                old: old_status,
+                new: new_status
+            });
+        }
+    }
+
+    Ok(DefinitionComparison {
+        badges,
+        users: user_comparisons
+    })
+}
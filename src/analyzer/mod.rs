@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use serenity::model::guild::Member;
 use crate::analyzer::role_definition::{BadgeDefinition, RoleDefinition};
 use crate::model::lumadb::verified_connections;
 use crate::boards::cm::CmBoardsState;
@@ -8,6 +7,10 @@ use crate::error::RoleManagerError;
 
 pub mod role_definition;
 pub mod user;
+pub mod validation;
+pub mod whatif;
+pub mod compare;
+pub mod pipeline;
 
 pub struct RoleDefinitionReport {
     definition: RoleDefinition,
@@ -57,9 +60,10 @@ struct BadgeAnalysis {
 
 pub async fn full_analysis(definition: RoleDefinition,
                            connections: Vec<verified_connections::Model>,
-                           users: Vec<Member>,
+                           user_ids: Vec<u64>,
                            srcom_state: SrComBoardsState,
-                           cm_state: CmBoardsState) -> Result<RoleDefinitionReport, RoleManagerError> {
+                           cm_state: CmBoardsState,
+                           workers: usize) -> Result<RoleDefinitionReport, RoleManagerError> {
     let mut report = RoleDefinitionReport::new(definition);
 
     // Set up analysis objects for each badge in the definition file
@@ -75,23 +79,9 @@ pub async fn full_analysis(definition: RoleDefinition,
         });
     }
 
-    let mut i = 0;
-    for user in &users {
-        if i % 100 == 0 {
-            println!("Analyzing user {}/{}", i, &users.len());
-        }
-        i += 1;
-
-        // Analyze the user
-        let analysis = user::analyze_user(
-            user.user.id.get(),
-            &report.definition,
-            &connections,
-            srcom_state.clone(),
-            cm_state.clone(),
-            false
-        ).await?;
+    let analyses = pipeline::analyze_users(&report.definition, &connections, &user_ids, &srcom_state, &cm_state, workers).await?;
 
+    for analysis in analyses {
         // Add to account counts
         report.total_users += 1;
         if analysis.external_accounts.iter()
@@ -120,3 +110,77 @@ pub async fn full_analysis(definition: RoleDefinition,
 
     Ok(report)
 }
+
+/// A CSV sheet of which users meet which requirements of a single badge
+pub struct BadgeReport {
+    pub csv: Vec<u8>,
+    pub users_meeting_badge: u64
+}
+
+/// Builds the sheet for `badge_name` from the users given as (discord id, name) pairs, or `None` if the
+/// definition has no such badge
+pub async fn badge_report(definition: &RoleDefinition,
+                          badge_name: &str,
+                          users: &[(u64, String)],
+                          connections: &[verified_connections::Model],
+                          srcom_state: SrComBoardsState,
+                          cm_state: CmBoardsState) -> Result<Option<BadgeReport>, RoleManagerError> {
+    // Look up the badge definition and build a header for our sheet with it
+    let badge_definition = match definition.badges.iter().find(|badge| badge.name == badge_name) {
+        Some(bd) => bd,
+        None => return Ok(None)
+    };
+    let mut header = vec!["Discord User".to_string(), "Num Reqs Satisfied".to_string()];
+    for req in badge_definition.requirements.iter() {
+        header.push(req.format(srcom_state.clone()).await?);
+    }
+
+    // Write a CSV report
+    let mut report = csv::Writer::from_writer(vec![]);
+    report.write_record(&header).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+
+    let mut users_meeting_badge = 0;
+
+    for (user_id, user_name) in users {
+        let analysis = user::analyze_user(
+            *user_id,
+            definition,
+            connections,
+            srcom_state.clone(),
+            cm_state.clone(),
+            false
+        ).await?;
+        let badge_analysis = match analysis.badges.iter().find(|analyzed_badge| analyzed_badge.definition == badge_definition) {
+            Some(badge_analysis) => badge_analysis,
+            None => continue
+        };
+
+        users_meeting_badge += 1;
+
+        // Build report row
+        let mut row = vec![];
+        row.push(user_name.clone());
+
+        let mut met_requirements = 0;
+        for req in &badge_definition.requirements {
+            if badge_analysis.met_requirements.iter().any(|met_req| *met_req.definition == *req) {
+                row.push("true".to_string());
+                met_requirements += 1;
+            } else {
+                row.push("false".to_string());
+            }
+        }
+
+        row.insert(1, format!("{}", met_requirements));
+
+        report.write_record(&row).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+    }
+
+    let csv = report.into_inner()
+        .map_err(|e| RoleManagerError::new(format!("Failed to generate report: {}", e)))?;
+
+    Ok(Some(BadgeReport {
+        csv,
+        users_meeting_badge
+    }))
+}
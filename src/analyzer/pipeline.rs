@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::{StreamExt, TryStreamExt};
+use futures::future::try_join_all;
+use crate::analyzer::role_definition::{srcom_board, RankRequirement, RankTimeRequirement, RecentRequirement, RequirementDefinition, RoleDefinition, TimeRequirement};
+use crate::analyzer::user::{analyze_user, AnalyzedUser};
+use crate::boards::cm::CmBoardsState;
+use crate::boards::srcom::SrComBoardsState;
+use crate::error::RoleManagerError;
+use crate::model::lumadb::verified_connections;
+
+/// Number of members analyzed at once unless configured otherwise
+pub const DEFAULT_ANALYSIS_WORKERS: usize = 8;
+
+type Prefetch<'a> = Pin<Box<dyn Future<Output = Result<(), RoleManagerError>> + Send + 'a>>;
+
+/// Analyzes every user, with up to `workers` analyses running at once. Every board the definition refers to is
+/// fetched up front, so the analyses themselves rarely wait on a download. Analyses are in the same order as `user_ids`.
+pub async fn analyze_users<'a>(
+    definition: &'a RoleDefinition,
+    connections: &[verified_connections::Model],
+    user_ids: &[u64],
+    srcom_state: &SrComBoardsState,
+    cm_state: &CmBoardsState,
+    workers: usize
+) -> Result<Vec<AnalyzedUser<'a>>, RoleManagerError> {
+    prefetch_boards(definition, srcom_state, cm_state).await?;
+
+    let analyzed = AtomicUsize::new(0);
+    futures::stream::iter(user_ids.iter().copied())
+        .map(|user_id| {
+            let analyzed = &analyzed;
+            async move {
+                let analysis = analyze_user(user_id, definition, connections, srcom_state.clone(), cm_state.clone(), false).await?;
+
+                let count = analyzed.fetch_add(1, Ordering::Relaxed) + 1;
+                if count.is_multiple_of(100) {
+                    println!("Analyzed user {}/{}", count, user_ids.len());
+                }
+
+                Ok(analysis)
+            }
+        })
+        .buffered(workers.max(1))
+        .try_collect()
+        .await
+}
+
+/// Fetches every leaderboard, aggregate, chamber and activity list referred to by the definition's requirements
+pub async fn prefetch_boards(definition: &RoleDefinition, srcom_state: &SrComBoardsState, cm_state: &CmBoardsState) -> Result<(), RoleManagerError> {
+    let mut prefetches = Vec::new();
+    for badge in &definition.badges {
+        for requirement in &badge.requirements {
+            requirement_prefetches(requirement, srcom_state, cm_state, &mut prefetches);
+        }
+    }
+
+    try_join_all(prefetches).await?;
+
+    Ok(())
+}
+
+fn requirement_prefetches<'a>(
+    requirement: &'a RequirementDefinition,
+    srcom_state: &'a SrComBoardsState,
+    cm_state: &'a CmBoardsState,
+    prefetches: &mut Vec<Prefetch<'a>>
+) {
+    match requirement {
+        RequirementDefinition::Rank(RankRequirement::Srcom { game, category, level, variables, filters, .. })
+        | RequirementDefinition::Time(TimeRequirement::Srcom { game, category, level, variables, filters, .. })
+        | RequirementDefinition::RankTime(RankTimeRequirement::Srcom { game, category, level, variables, filters, .. }) => {
+            prefetches.push(Box::pin(async move {
+                srcom_state.fetch_board(srcom_board(game, category, level, variables, filters)).await?;
+                Ok(())
+            }));
+        }
+        // Recent runs are looked up on the leaderboard first, and only fetched per user when that run is too old
+        RequirementDefinition::Recent(RecentRequirement::Srcom { game, category, level, variables, filters, .. }) => {
+            prefetches.push(Box::pin(async move {
+                srcom_state.fetch_board(srcom_board(game, category, level, variables, filters)).await?;
+                Ok(())
+            }));
+        }
+        RequirementDefinition::Points { leaderboard, .. } => {
+            prefetches.push(Box::pin(async move {
+                cm_state.fetch_aggregate(leaderboard).await?;
+                Ok(())
+            }));
+        }
+        RequirementDefinition::Recent(RecentRequirement::Cm { months }) => {
+            prefetches.push(Box::pin(async move {
+                cm_state.fetch_active_profiles(*months).await?;
+                Ok(())
+            }));
+        }
+        RequirementDefinition::CmChamber(req) => {
+            prefetches.push(Box::pin(async move {
+                cm_state.fetch_chamber(req.chamber).await?;
+                Ok(())
+            }));
+        }
+        RequirementDefinition::All { of } | RequirementDefinition::Any { of } | RequirementDefinition::AtLeast { of, .. } => {
+            for nested in of {
+                requirement_prefetches(nested, srcom_state, cm_state, prefetches);
+            }
+        }
+        RequirementDefinition::Manual => {}
+    }
+}
@@ -1,8 +1,10 @@
 use std::fmt::{Display, Formatter};
 use serde::{Deserialize};
-use crate::boards::srcom::SrComBoardsState;
+use crate::boards::srcom::{BoardDefinition, SrComBoardsState, TimingMethod};
+use crate::boards::srcom::leaderboard::BoardFilters;
 use crate::boards::srcom::category::CategoryId;
 use crate::boards::srcom::game::GameId;
+use crate::boards::srcom::level::LevelId;
 use crate::boards::srcom::variable::{VariableId, VariableValueId};
 use crate::error::RoleManagerError;
 
@@ -11,6 +13,36 @@ pub struct RoleDefinition {
     pub badges: Vec<BadgeDefinition>
 }
 
+/// Badges which differ between two definitions, matched by name
+#[derive(Debug, Default)]
+pub struct DefinitionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>
+}
+
+impl RoleDefinition {
+    pub fn diff(&self, new: &RoleDefinition) -> DefinitionDiff {
+        let mut diff = DefinitionDiff::default();
+
+        for badge in &new.badges {
+            match self.badges.iter().find(|old| old.name == badge.name) {
+                Some(old) if old.requirements != badge.requirements => diff.changed.push(badge.name.clone()),
+                Some(_) => {}
+                None => diff.added.push(badge.name.clone())
+            }
+        }
+
+        for badge in &self.badges {
+            if !new.badges.iter().any(|b| b.name == badge.name) {
+                diff.removed.push(badge.name.clone());
+            }
+        }
+
+        diff
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct BadgeDefinition {
     pub name: String,
@@ -20,7 +52,7 @@ pub struct BadgeDefinition {
 impl BadgeDefinition {
     pub fn can_autoremove(&self) -> bool {
         for req in &self.requirements {
-            if let RequirementDefinition::Manual = req {
+            if req.contains_manual() {
                 return false;
             }
         }
@@ -45,30 +77,76 @@ pub enum RequirementDefinition {
         points: u64
     },
     #[serde(rename = "recent")]
-    Recent(RecentRequirement)
+    Recent(RecentRequirement),
+    #[serde(rename = "cm_chamber")]
+    CmChamber(CmChamberRequirement),
+    #[serde(rename = "all")]
+    All {
+        of: Vec<RequirementDefinition>
+    },
+    #[serde(rename = "any")]
+    Any {
+        of: Vec<RequirementDefinition>
+    },
+    #[serde(rename = "at_least")]
+    AtLeast {
+        n: u64,
+        of: Vec<RequirementDefinition>
+    }
 }
 
 impl RequirementDefinition {
+    /// Whether this requirement, or any requirement nested in it, is manually assigned
+    pub fn contains_manual(&self) -> bool {
+        match self {
+            Self::Manual => true,
+            Self::All { of } | Self::Any { of } | Self::AtLeast { of, .. } => of.iter().any(|req| req.contains_manual()),
+            _ => false
+        }
+    }
+
+    /// The nested requirements of a group, along with how many of them must be met
+    pub fn group(&self) -> Option<(&Vec<RequirementDefinition>, usize)> {
+        match self {
+            Self::All { of } => Some((of, of.len())),
+            Self::Any { of } => Some((of, 1)),
+            Self::AtLeast { n, of } => Some((of, *n as usize)),
+            _ => None
+        }
+    }
+
     pub fn short_description(&self) -> String {
         match self {
             Self::Manual => format!("Manual"),
-            Self::Rank(RankRequirement::Srcom { game, category, variables, top, partner}) => {
+            Self::Rank(RankRequirement::Srcom { game, category, variables, top, partner, .. }) => {
                 format!("SRC {} Top {}", game.0, top)
             },
-            Self::Time(TimeRequirement::Srcom { game, category, variables, time, partner}) => {
-                format!("SRC {} Sub {}", game.0, time)
+            Self::Time(TimeRequirement::Srcom { game, category, variables, time, timing, partner, .. }) => {
+                format!("SRC {} Sub {}", game.0, time_threshold(time, timing))
             },
-            Self::RankTime(RankTimeRequirement::Srcom { game, category, variables, time, top, partner}) => {
-                format!("SRC {} Sub {} AND Top {}", game.0, time, top)
+            Self::RankTime(RankTimeRequirement::Srcom { game, category, variables, time, timing, top, partner, .. }) => {
+                format!("SRC {} Sub {} AND Top {}", game.0, time_threshold(time, timing), top)
             },
             Self::Points { leaderboard, points } => {
                 format!("{} {}p", leaderboard, points)
             },
-            Self::Recent(RecentRequirement::Srcom {game, category, variables, months}) => {
+            Self::Recent(RecentRequirement::Srcom {game, category, variables, months, .. }) => {
                 format!("SRC {} Recent", game.0)
             },
             Self::Recent(RecentRequirement::Cm {months}) => {
                 format!("CM Recent")
+            },
+            Self::CmChamber(req) => {
+                format!("CM {} {}", req.label(), req.threshold_description())
+            },
+            Self::All { of } => {
+                format!("All({})", of.iter().map(|req| req.short_description()).collect::<Vec<_>>().join(" & "))
+            },
+            Self::Any { of } => {
+                format!("Any({})", of.iter().map(|req| req.short_description()).collect::<Vec<_>>().join(" | "))
+            },
+            Self::AtLeast { n, of } => {
+                format!("{} of ({})", n, of.iter().map(|req| req.short_description()).collect::<Vec<_>>().join(", "))
             }
         }
     }
@@ -76,9 +154,9 @@ impl RequirementDefinition {
     pub async fn format(&self, srcom_state: SrComBoardsState) -> Result<String, RoleManagerError> {
         Ok(match self {
             Self::Manual => format!("Manual"),
-            Self::Rank(RankRequirement::Srcom { game, category, variables, top, partner }) => {
+            Self::Rank(RankRequirement::Srcom { game, category, level, variables, filters, top, partner }) => {
                 let game = srcom_state.fetch_game(game.clone()).await?;
-                let category = srcom_state.fetch_category(category.clone()).await?;
+                let board_name = board_name(&srcom_state, category, level).await?;
 
                 let mut variable_descs = vec![];
                 for id_pair in variables.as_ref().unwrap_or(&vec![]) {
@@ -95,16 +173,17 @@ impl RequirementDefinition {
                     Some(PartnerRestriction::RankGte) => " (partner.rank>=player.rank)",
                     None => ""
                 };
+                let restriction = format!("{}{}", restriction, filters_description(filters));
 
                 if variable_descs.is_empty() {
-                    format!("SRC - {} - {} - Top {}{}", game.names.international, category.name, top, restriction)
+                    format!("SRC - {} - {} - Top {}{}", game.names.international, board_name, top, restriction)
                 } else {
-                    format!("SRC - {} - {} ({}) - Top {}{}", game.names.international, category.name, variable_descs.join(","), top, restriction)
+                    format!("SRC - {} - {} ({}) - Top {}{}", game.names.international, board_name, variable_descs.join(","), top, restriction)
                 }
             },
-            Self::Time(TimeRequirement::Srcom { game, category, variables, time, partner}) => {
+            Self::Time(TimeRequirement::Srcom { game, category, level, variables, filters, time, timing, partner}) => {
                 let game = srcom_state.fetch_game(game.clone()).await?;
-                let category = srcom_state.fetch_category(category.clone()).await?;
+                let board_name = board_name(&srcom_state, category, level).await?;
 
                 let mut variable_descs = vec![];
                 for id_pair in variables.as_ref().unwrap_or(&vec![]) {
@@ -121,16 +200,18 @@ impl RequirementDefinition {
                     Some(PartnerRestriction::RankGte) => " (partner.rank>=player.rank)",
                     None => ""
                 };
+                let restriction = format!("{}{}", restriction, filters_description(filters));
 
+                let time = time_threshold(time, timing);
                 if variable_descs.is_empty() {
-                    format!("SRC - {} - {} - Sub {}{}", game.names.international, category.name, time, restriction)
+                    format!("SRC - {} - {} - Sub {}{}", game.names.international, board_name, time, restriction)
                 } else {
-                    format!("SRC - {} - {} ({}) - Sub {}{}", game.names.international, category.name, variable_descs.join(","), time, restriction)
+                    format!("SRC - {} - {} ({}) - Sub {}{}", game.names.international, board_name, variable_descs.join(","), time, restriction)
                 }
             },
-            Self::RankTime(RankTimeRequirement::Srcom {game, category, variables, time, top, partner}) => {
+            Self::RankTime(RankTimeRequirement::Srcom { game, category, level, variables, filters, time, timing, top, partner}) => {
                 let game = srcom_state.fetch_game(game.clone()).await?;
-                let category = srcom_state.fetch_category(category.clone()).await?;
+                let board_name = board_name(&srcom_state, category, level).await?;
 
                 let mut variable_descs = vec![];
                 for id_pair in variables.as_ref().unwrap_or(&vec![]) {
@@ -147,11 +228,13 @@ impl RequirementDefinition {
                     Some(PartnerRestriction::RankGte) => " (partner.rank>=player.rank)",
                     None => ""
                 };
+                let restriction = format!("{}{}", restriction, filters_description(filters));
 
+                let time = time_threshold(time, timing);
                 if variable_descs.is_empty() {
-                    format!("SRC - {} - {} - Sub {} AND Top {}{}", game.names.international, category.name, time, top, restriction)
+                    format!("SRC - {} - {} - Sub {} AND Top {}{}", game.names.international, board_name, time, top, restriction)
                 } else {
-                    format!("SRC - {} - {} ({}) - Sub {} AND Top{}{}", game.names.international, category.name, variable_descs.join(","), time, top, restriction)
+                    format!("SRC - {} - {} ({}) - Sub {} AND Top{}{}", game.names.international, board_name, variable_descs.join(","), time, top, restriction)
                 }
             },
             Self::Points { leaderboard, points } => {
@@ -160,9 +243,9 @@ impl RequirementDefinition {
             Self::Recent(RecentRequirement::Cm { months}) => {
                 format!("CM - Activity in last {} months", months)
             }
-            Self::Recent(RecentRequirement::Srcom { game, category, variables, months}) => {
+            Self::Recent(RecentRequirement::Srcom { game, category, level, variables, filters, months}) => {
                 let game = srcom_state.fetch_game(game.clone()).await?;
-                let category = srcom_state.fetch_category(category.clone()).await?;
+                let board_name = board_name(&srcom_state, category, level).await?;
 
                 let mut variable_descs = vec![];
                 for id_pair in variables.as_ref().unwrap_or(&vec![]) {
@@ -175,16 +258,90 @@ impl RequirementDefinition {
                     variable_descs.push(format!("{}", value.label));
                 }
 
+                let restriction = filters_description(filters);
+
                 if variable_descs.is_empty() {
-                    format!("SRC - {} - {} - Activity in last {} months", game.names.international, category.name, months)
+                    format!("SRC - {} - {} - Activity in last {} months{}", game.names.international, board_name, months, restriction)
                 } else {
-                    format!("SRC - {} - {} ({}) - Activity in last {} months", game.names.international, category.name, variable_descs.join(","), months)
+                    format!("SRC - {} - {} ({}) - Activity in last {} months{}", game.names.international, board_name, variable_descs.join(","), months, restriction)
+                }
+            },
+            Self::CmChamber(req) => {
+                match &req.chapter {
+                    Some(chapter) => format!("CM - {} - {} - {}", chapter, req.label(), req.threshold_description()),
+                    None => format!("CM - {} - {}", req.label(), req.threshold_description())
                 }
+            },
+            Self::All { .. } | Self::Any { .. } | Self::AtLeast { .. } => {
+                let (of, _) = self.group().unwrap();
+
+                let mut descs = vec![];
+                for req in of {
+                    descs.push(Box::pin(req.format(srcom_state.clone())).await?);
+                }
+
+                // Taken from the group's type, since the counts alone can't tell a one requirement `any` from an `all`
+                let quantifier = match self {
+                    Self::AtLeast { n, .. } => format!("At least {}", n),
+                    Self::Any { .. } => "Any".to_string(),
+                    _ => "All".to_string()
+                };
+
+                format!("{} of: ({})", quantifier, descs.join("; "))
             }
         })
     }
 }
 
+/// Describes the filters narrowing down a board, if there are any
+fn filters_description(filters: &Option<BoardFilters>) -> String {
+    match filters.as_ref().map(|filters| filters.to_string()) {
+        Some(description) if !description.is_empty() => format!(" [{}]", description),
+        _ => String::new()
+    }
+}
+
+/// The speedrun.com board which a requirement refers to
+pub fn srcom_board(
+    game: &GameId,
+    category: &CategoryId,
+    level: &Option<LevelId>,
+    variables: &Option<Vec<VariableDefinition>>,
+    filters: &Option<BoardFilters>
+) -> BoardDefinition {
+    BoardDefinition {
+        game: game.clone(),
+        category: category.clone(),
+        level: level.clone(),
+        variables: variables.iter().flatten()
+            .map(|var| (var.variable.clone(), var.choice.clone()))
+            .collect(),
+        filters: filters.clone().unwrap_or_default()
+    }
+}
+
+/// The timing method a requirement's times are compared and shown in. Without a timing of its own, that's the one
+/// its filters rank the board by, or otherwise the board's primary timing.
+pub fn compared_timing(timing: &Option<TimingMethod>, filters: &Option<BoardFilters>) -> Option<TimingMethod> {
+    timing.or(filters.as_ref().and_then(|filters| filters.timing))
+}
+
+/// A time threshold, along with the timing method it's compared in unless that's the board's primary timing
+fn time_threshold(time: &str, timing: &Option<TimingMethod>) -> String {
+    match timing {
+        Some(timing) => format!("{} {}", time, timing),
+        None => time.to_string()
+    }
+}
+
+/// The name shown for a speedrun.com board, which is the level for IL boards and the category otherwise
+async fn board_name(srcom_state: &SrComBoardsState, category: &CategoryId, level: &Option<LevelId>) -> Result<String, RoleManagerError> {
+    Ok(match level {
+        Some(level) => srcom_state.fetch_level(level.clone()).await?.name.clone(),
+        None => srcom_state.fetch_category(category.clone()).await?.name.clone()
+    })
+}
+
 #[derive(Deserialize, Debug, Copy, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub enum CmLeaderboard {
     #[serde(rename = "aggregated/overall")]
@@ -226,7 +383,9 @@ pub enum RecentRequirement {
     Srcom {
         game: GameId,
         category: CategoryId,
+        level: Option<LevelId>,
         variables: Option<Vec<VariableDefinition>>,
+        filters: Option<BoardFilters>,
         months: u64
     },
     #[serde(rename = "cm")]
@@ -235,6 +394,35 @@ pub enum RecentRequirement {
     }
 }
 
+#[derive(Deserialize, Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct CmChamberRequirement {
+    pub chamber: u64,
+    /// Display name of the chamber, since the boards only expose chamber ids
+    pub name: Option<String>,
+    /// Display name of the chapter containing the chamber
+    pub chapter: Option<String>,
+    pub top: Option<u64>,
+    pub time: Option<String>
+}
+
+impl CmChamberRequirement {
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Chamber {}", self.chamber)
+        }
+    }
+
+    fn threshold_description(&self) -> String {
+        match (&self.time, &self.top) {
+            (Some(time), Some(top)) => format!("Sub {} AND Top {}", time, top),
+            (Some(time), None) => format!("Sub {}", time),
+            (None, Some(top)) => format!("Top {}", top),
+            (None, None) => "Any Run".to_string()
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
 #[serde(tag = "platform")]
 pub enum RankRequirement {
@@ -242,7 +430,9 @@ pub enum RankRequirement {
     Srcom {
         game: GameId,
         category: CategoryId,
+        level: Option<LevelId>,
         variables: Option<Vec<VariableDefinition>>,
+        filters: Option<BoardFilters>,
         partner: Option<PartnerRestriction>,
         top: u64
     }
@@ -255,9 +445,13 @@ pub enum TimeRequirement {
     Srcom {
         game: GameId,
         category: CategoryId,
+        level: Option<LevelId>,
         variables: Option<Vec<VariableDefinition>>,
+        filters: Option<BoardFilters>,
         partner: Option<PartnerRestriction>,
-        time: String
+        time: String,
+        /// Timing method the time is compared in, instead of the board's primary timing
+        timing: Option<TimingMethod>
     }
 }
 
@@ -269,9 +463,13 @@ pub enum RankTimeRequirement {
     Srcom {
         game: GameId,
         category: CategoryId,
+        level: Option<LevelId>,
         variables: Option<Vec<VariableDefinition>>,
+        filters: Option<BoardFilters>,
         partner: Option<PartnerRestriction>,
         time: String,
+        /// Timing method the time is compared in, instead of the board's primary timing
+        timing: Option<TimingMethod>,
         top: u64
     }
 }
@@ -1,13 +1,14 @@
-use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
-use chrono::{Duration, NaiveDateTime, Utc};
-use crate::analyzer::role_definition::{BadgeDefinition, CmLeaderboard, RankRequirement, RankTimeRequirement, RecentRequirement, RequirementDefinition, RoleDefinition, TimeRequirement};
+use std::sync::Arc;
+use chrono::{Months, NaiveDate, NaiveDateTime, Utc};
+use crate::analyzer::role_definition::{compared_timing, srcom_board, BadgeDefinition, CmChamberRequirement, CmLeaderboard, PartnerRestriction, Platform, RankRequirement, RankTimeRequirement, RecentRequirement, RequirementDefinition, RoleDefinition, TimeRequirement};
 use crate::analyzer::user::MetRequirementCause::CmActivity;
 use crate::boards::srcom::leaderboard::LeaderboardPlace;
-use crate::boards::srcom::SrComBoardsState;
+use crate::boards::srcom::run::Run;
+use crate::boards::srcom::{BoardDefinition, SrComBoardsState, TimingMethod};
 use crate::boards::srcom::user::UserId;
-use crate::boards::srcom::variable::{VariableId, VariableValueId};
 use crate::boards::cm::CmBoardsState;
+use crate::boards::cm::chamber::ChamberPlace;
 use crate::error::RoleManagerError;
 use crate::model::lumadb::verified_connections;
 
@@ -37,6 +38,12 @@ pub enum MetRequirementCause {
         time: String,
         achieved_on: speedate::Date,
     },
+    RecentRun {
+        srcom_id: UserId,
+        link: String,
+        time: String,
+        achieved_on: NaiveDate
+    },
     CmAggregate {
         steam_id: i64,
         board: CmLeaderboard,
@@ -52,6 +59,11 @@ pub enum MetRequirementCause {
     },
     CmActivity {
         steam_id: i64
+    },
+    Group {
+        met: usize,
+        needed: usize,
+        total: usize
     }
 }
 
@@ -67,6 +79,9 @@ impl Display for MetRequirementCause {
             Self::FullgameRun { link, rank, time, .. } => {
                 write!(f, "[#{} - {}]({})", rank, time, link)
             }
+            Self::RecentRun { link, time, achieved_on, .. } => {
+                write!(f, "[{} on {}]({})", time, achieved_on, link)
+            }
             Self::CmAggregate { steam_id, board, points } => {
                 write!(f, "[{} - {}](https://board.portal2.sr/profile/{})", board, points, steam_id)
             }
@@ -76,11 +91,43 @@ impl Display for MetRequirementCause {
             Self::CmActivity { steam_id } => {
                 write!(f, "[CM Activity](https://board.portal2.sr/profile/{})", steam_id)
             }
+            Self::Group { met, needed, total } => {
+                write!(f, "{}/{} met ({} needed)", met, total, needed)
+            }
         }
     }
 }
 
-fn fullgame_cause(user: &UserId, place: &LeaderboardPlace) -> Result<MetRequirementCause, RoleManagerError> {
+/// Formats a speedrun.com run time given in seconds
+fn format_run_time(primary_t: f64) -> String {
+    let total_seconds = primary_t as u64;
+    let hours = total_seconds / (60 * 60);
+    let minutes = (total_seconds % (60 * 60)) / 60;
+    let seconds = total_seconds % 60;
+    let milliseconds = ((primary_t - (total_seconds as f64)) * 1_000.0) as u64;
+
+    if hours == 0 {
+        format!("{}:{:02}.{:03}", minutes, seconds, milliseconds)
+    } else {
+        format!("{}:{:02}:{:02}.{:03}", hours, minutes, seconds, milliseconds)
+    }
+}
+
+/// Formats a CM score, which is stored in centiseconds
+fn format_cm_time(score: u32) -> String {
+    let minutes = score / (60 * 100);
+    let seconds = (score % (60 * 100)) / 100;
+    let centiseconds = score % 100;
+
+    if minutes == 0 {
+        format!("{}.{:02}", seconds, centiseconds)
+    } else {
+        format!("{}:{:02}.{:02}", minutes, seconds, centiseconds)
+    }
+}
+
+/// What met a requirement on a speedrun.com board, with the run's time shown by `timing` if one is set
+fn fullgame_cause(user: &UserId, place: &LeaderboardPlace, timing: Option<TimingMethod>) -> Result<MetRequirementCause, RoleManagerError> {
     let date = match &place.run.date {
         Some(d) => {
             speedate::Date::parse_str(d.as_str())
@@ -92,56 +139,281 @@ fn fullgame_cause(user: &UserId, place: &LeaderboardPlace) -> Result<MetRequirem
         }
     };
 
-    let total_seconds = place.run.times.primary_t as u64;
-    let hours = total_seconds / (60 * 60);
-    let minutes = (total_seconds % (60 * 60)) / 60;
-    let seconds = total_seconds % 60;
-    let milliseconds = ((place.run.times.primary_t - (total_seconds as f64)) * 1_000.0) as u64;
-
-    let duration = if hours == 0 {
-        format!("{}:{:02}.{:03}", minutes, seconds, milliseconds)
-    } else {
-        format!("{}:{:02}:{:02}.{:03}", hours, minutes, seconds, milliseconds)
-    };
-
     Ok(MetRequirementCause::FullgameRun {
         srcom_id: user.clone(),
         link: (&place.run.weblink).clone(),
         rank: place.place as u32,
-        time: duration,
+        time: format_run_time(place.run.times.seconds(timing).unwrap_or(place.run.times.primary_t)),
         achieved_on: date
     })
 }
 
+fn cm_run_cause(steam_id: i64, requirement: &CmChamberRequirement, place: &ChamberPlace) -> Result<MetRequirementCause, RoleManagerError> {
+    let achieved_on = match &place.score_data.date {
+        Some(d) => NaiveDateTime::parse_from_str(d.as_str(), "%Y-%m-%d %H:%M:%S")
+            .map_err(|err| RoleManagerError::new(format!("CM Boards returned an invalid date format: {} (caused by {})", d, err)))?,
+        None => Utc::now().naive_utc()
+    };
+
+    Ok(MetRequirementCause::CmRun {
+        steam_id,
+        chapter: requirement.chapter.clone().unwrap_or_else(|| "CM".to_string()),
+        chamber: requirement.label(),
+        rank: place.score_data.player_rank,
+        time: format_cm_time(place.score_data.score),
+        achieved_on
+    })
+}
+
 #[derive(Debug)]
 pub struct MetRequirement<'a> {
     pub definition: &'a RequirementDefinition,
-    pub cause: MetRequirementCause
+    pub cause: MetRequirementCause,
+    /// For requirement groups, the nested requirements that satisfied the group
+    pub branches: Vec<MetRequirement<'a>>
+}
+
+impl MetRequirement<'_> {
+    /// Describes this requirement and what met it, including the branches which satisfied any requirement groups
+    pub async fn describe(&self, srcom_state: SrComBoardsState, depth: usize) -> Result<String, RoleManagerError> {
+        let indent = "  ".repeat(depth);
+
+        if self.definition.group().is_none() {
+            return Ok(format!("{}{}\n{} - {}", indent, self.definition.format(srcom_state).await?, indent, self.cause));
+        }
+
+        let mut lines = vec![format!("{}Requirement group - {}", indent, self.cause)];
+        for branch in &self.branches {
+            lines.push(Box::pin(branch.describe(srcom_state.clone(), depth + 1)).await?);
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+/// What is still missing for a requirement which isn't met
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequirementGap {
+    Manual,
+    NoAccount {
+        platform: Platform
+    },
+    NoRun,
+    /// The best run wasn't timed in the requirement's timing method
+    NoTiming {
+        timing: TimingMethod
+    },
+    Rank {
+        rank: u64,
+        needed: u64
+    },
+    Time {
+        time: String,
+        needed: String
+    },
+    RankTime {
+        rank: u64,
+        time: String,
+        needed_rank: u64,
+        needed_time: String
+    },
+    Points {
+        points: u64,
+        needed: u64
+    },
+    LastRun {
+        date: String
+    },
+    Inactive {
+        months: u64
+    },
+    Group {
+        met: usize,
+        needed: usize,
+        total: usize
+    }
+}
+
+impl Display for RequirementGap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Manual => write!(f, "Assigned manually"),
+            Self::NoAccount { platform: Platform::Srcom } => write!(f, "No linked speedrun.com account"),
+            Self::NoAccount { platform: Platform::Cm } => write!(f, "No linked steam account"),
+            Self::NoRun => write!(f, "No run on this leaderboard"),
+            Self::NoTiming { timing } => write!(f, "Best run has no {} time", timing),
+            Self::Rank { rank, needed } => write!(f, "Rank {}, need top {}", rank, needed),
+            Self::Time { time, needed } => write!(f, "{} vs {}", time, needed),
+            Self::RankTime { rank, time, needed_rank, needed_time } => {
+                write!(f, "Rank {} in {}, need top {} under {}", rank, time, needed_rank, needed_time)
+            }
+            Self::Points { points, needed } => {
+                write!(f, "{} / {} points", thousands(*points), thousands(*needed))
+            }
+            Self::LastRun { date } => write!(f, "Last run on {}", date),
+            Self::Inactive { months } => write!(f, "No activity in the last {} months", months),
+            Self::Group { met, needed, total } => write!(f, "{}/{} met ({} needed)", met, total, needed)
+        }
+    }
+}
+
+impl RequirementGap {
+    /// How close the user is to meeting the requirement, from 0 (nowhere near) up to 1. Gaps without anything to
+    /// measure, such as missing accounts, count as 0.
+    pub fn progress(&self) -> f64 {
+        let ratio = |have: f64, need: f64| if have > 0.0 { (need / have).clamp(0.0, 1.0) } else { 0.0 };
+        let time_ratio = |time: &str, needed: &str| match (clock_seconds(time), clock_seconds(needed)) {
+            (Some(time), Some(needed)) => ratio(time, needed),
+            _ => 0.0
+        };
+
+        match self {
+            Self::Rank { rank, needed } => ratio(*rank as f64, *needed as f64),
+            Self::Time { time, needed } => time_ratio(time, needed),
+            Self::RankTime { rank, time, needed_rank, needed_time } => {
+                (ratio(*rank as f64, *needed_rank as f64) + time_ratio(time, needed_time)) / 2.0
+            }
+            Self::Points { points, needed } => (*points as f64 / (*needed).max(1) as f64).clamp(0.0, 1.0),
+            Self::Group { met, needed, .. } => (*met as f64 / (*needed).max(1) as f64).clamp(0.0, 1.0),
+            Self::Manual | Self::NoAccount { .. } | Self::NoRun | Self::NoTiming { .. } | Self::LastRun { .. } | Self::Inactive { .. } => 0.0
+        }
+    }
+}
+
+/// Reads a time such as `1:00:50.000`, `01:00:00` or `10.25` as seconds
+fn clock_seconds(time: &str) -> Option<f64> {
+    time.split(':')
+        .try_fold(0.0, |seconds, part| part.trim().parse::<f64>().ok().map(|part| seconds * 60.0 + part))
+}
+
+/// Formats a number with comma separators, e.g. 11,500
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+
+    formatted
+}
+
+#[derive(Debug)]
+pub struct UnmetRequirement<'a> {
+    pub definition: &'a RequirementDefinition,
+    /// What is still missing, once [`AnalyzedUser::compute_gaps`] has worked it out
+    pub gap: Option<RequirementGap>,
+    /// For requirement groups, the nested requirements which aren't met
+    pub branches: Vec<UnmetRequirement<'a>>
+}
+
+impl UnmetRequirement<'_> {
+    /// How close the user is to meeting this requirement, from 0 up to 1. Groups count their closest unmet branches
+    /// towards the branches they still need.
+    pub fn progress(&self) -> f64 {
+        match (&self.gap, self.definition.group()) {
+            (Some(RequirementGap::Group { met, needed, .. }), Some(_)) => {
+                let mut closest: Vec<f64> = self.branches.iter().map(|branch| branch.progress()).collect();
+                closest.sort_by(|a, b| b.total_cmp(a));
+
+                let missing = needed.saturating_sub(*met);
+                let partial: f64 = closest.iter().take(missing).sum();
+                ((*met as f64 + partial) / (*needed).max(1) as f64).clamp(0.0, 1.0)
+            }
+            (Some(gap), _) => gap.progress(),
+            (None, _) => 0.0
+        }
+    }
+
+    /// Describes this requirement and what is missing for it, including the unmet branches of any requirement groups
+    pub async fn describe(&self, srcom_state: SrComBoardsState, depth: usize) -> Result<String, RoleManagerError> {
+        let indent = "  ".repeat(depth);
+        let gap = self.gap.as_ref().map(|gap| gap.to_string()).unwrap_or_else(|| "Not met".to_string());
+
+        if self.definition.group().is_none() {
+            return Ok(format!("{}- {} - {}", indent, self.definition.format(srcom_state).await?, gap));
+        }
+
+        let mut lines = vec![format!("{}- Requirement group - {}", indent, gap)];
+        for branch in &self.branches {
+            lines.push(Box::pin(branch.describe(srcom_state.clone(), depth + 1)).await?);
+        }
+
+        Ok(lines.join("\n"))
+    }
 }
 
 #[derive(Debug)]
 pub struct AnalyzedUserBadge<'a> {
     pub definition: &'a BadgeDefinition,
-    pub met_requirements: Vec<MetRequirement<'a>>
+    pub met_requirements: Vec<MetRequirement<'a>>,
+    pub unmet_requirements: Vec<UnmetRequirement<'a>>
 }
 
 impl AnalyzedUserBadge<'_> {
     pub fn is_complete(&self) -> bool {
         return self.met_requirements.len() == self.definition.requirements.len();
     }
+
+    /// Share of the badge's requirements which are met, with unmet requirements counting for how close they are
+    /// once their gaps are computed
+    pub fn progress(&self) -> f64 {
+        let unmet: f64 = self.unmet_requirements.iter().map(|unmet| unmet.progress()).sum();
+        (self.met_requirements.len() as f64 + unmet) / self.definition.requirements.len().max(1) as f64
+    }
 }
 
 #[derive(Debug)]
 pub struct AnalyzedUser<'a> {
     pub discord_id: u64,
     pub external_accounts: Vec<ExternalAccount>,
-    pub badges: Vec<AnalyzedUserBadge<'a>>
+    /// Badges with at least one met requirement
+    pub badges: Vec<AnalyzedUserBadge<'a>>,
+    /// Badges without any met requirements
+    pub unearned_badges: Vec<AnalyzedUserBadge<'a>>
+}
+
+impl<'a> AnalyzedUser<'a> {
+    /// Works out what is missing for every unmet requirement. This may download more of the boards, so it's only
+    /// done for analyses which show the gaps.
+    pub async fn compute_gaps(&mut self, srcom_boards: &SrComBoardsState, cm_boards: &CmBoardsState) -> Result<(), RoleManagerError> {
+        let mut steam_ids = Vec::new();
+        let mut srcom_ids = Vec::new();
+        for account in &self.external_accounts {
+            match account {
+                ExternalAccount::Cm { id, .. } => steam_ids.push(*id),
+                ExternalAccount::Srcom { id, .. } => srcom_ids.push(*id)
+            }
+        }
+
+        for badge in self.badges.iter_mut().chain(self.unearned_badges.iter_mut()) {
+            for unmet in &mut badge.unmet_requirements {
+                *unmet = unmet_requirement(unmet.definition, &badge.definition.name, &steam_ids, &srcom_ids, srcom_boards, cm_boards).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The badge which isn't complete yet and is closest to being completed. Gaps are taken into account once
+    /// they're computed, otherwise only the share of met requirements counts.
+    pub fn nearest_incomplete_badge(&self) -> Option<&AnalyzedUserBadge<'a>> {
+        self.badges.iter()
+            .chain(self.unearned_badges.iter())
+            .filter(|badge| !badge.is_complete())
+            .fold(None, |nearest: Option<&AnalyzedUserBadge<'a>>, badge| match nearest {
+                Some(nearest) if nearest.progress() >= badge.progress() => Some(nearest),
+                _ => Some(badge)
+            })
+    }
 }
 
 pub async fn analyze_user<'a>(
     discord_id: u64,
     role_definition: &'a RoleDefinition,
-    connections: &Vec<verified_connections::Model>,
+    connections: &[verified_connections::Model],
     srcom_boards: SrComBoardsState,
     cm_boards: CmBoardsState,
     requires_external_details: bool
@@ -166,218 +438,32 @@ pub async fn analyze_user<'a>(
 
     // Process each badge
     let mut analyzed_badges: Vec<AnalyzedUserBadge> = Vec::new();
+    let mut unearned_badges: Vec<AnalyzedUserBadge> = Vec::new();
 
     for badge_definition in &(role_definition.badges) {
         let mut met_requirements: Vec<MetRequirement> = Vec::new();
+        let mut unmet_requirements: Vec<UnmetRequirement> = Vec::new();
 
         for requirement in &badge_definition.requirements {
-            match requirement {
-                RequirementDefinition::Rank(req) => {
-                    match req {
-                        RankRequirement::Srcom { game, category, variables, top, partner } => {
-                            let mut variable_map = BTreeMap::new();
-                            match variables {
-                                Some(v) => {
-                                    for var in v {
-                                        variable_map.insert(var.variable.clone(), var.choice.clone());
-                                    }
-                                }
-                                None => {}
-                            }
-
-                            for srcom in &srcom_ids {
-                                match srcom_boards.fetch_user_highest_run(
-                                    *srcom,
-                                    *partner,
-                                    game.clone(),
-                                    category.clone(),
-                                    variable_map.clone()
-                                ).await? {
-                                    Some(run) => {
-                                        if run.place <= *top {
-                                            met_requirements.push(MetRequirement {
-                                                definition: requirement,
-                                                cause: fullgame_cause(srcom, &run)?
-                                            });
-                                            break;
-                                        }
-                                    }
-                                    None => {}
-                                }
-                            }
-                        }
-                    }
-                }
-                RequirementDefinition::Time(req) => {
-                    match req {
-                        TimeRequirement::Srcom { game, category, variables, time, partner } => {
-                            let seconds = speedate::Duration::parse_str(time.as_str())
-                                .map_err(|err| RoleManagerError::new(format!("Invalid duration specified in badge {}, {} (caused by {:?})", badge_definition.name, time, err)))?
-                                .signed_total_seconds();
-
-                            let mut variable_map = BTreeMap::new();
-                            match variables {
-                                Some(v) => {
-                                    for var in v {
-                                        variable_map.insert(var.variable.clone(), var.choice.clone());
-                                    }
-                                }
-                                None => {}
-                            }
-
-                            for srcom in &srcom_ids {
-                                match srcom_boards.fetch_user_highest_run(
-                                    *srcom,
-                                    *partner,
-                                    game.clone(),
-                                    category.clone(),
-                                    variable_map.clone()
-                                ).await? {
-                                    Some(run) => {
-                                        if run.run.times.primary_t <= seconds as f64 {
-                                            met_requirements.push(MetRequirement {
-                                                definition: requirement,
-                                                cause: fullgame_cause(srcom, &run)?
-                                            });
-                                            break;
-                                        }
-                                    }
-                                    None => {}
-                                }
-                            }
-                        }
-                    }
-                }
-                RequirementDefinition::RankTime(req) => {
-                    match req {
-                        RankTimeRequirement::Srcom { game, category, variables, time, top, partner } => {
-                            let seconds = speedate::Duration::parse_str(time.as_str())
-                                .map_err(|err| RoleManagerError::new(format!("Invalid duration specified in badge {}, {} (caused by {:?})", badge_definition.name, time, err)))?
-                                .signed_total_seconds();
-
-                            let mut variable_map = BTreeMap::new();
-                            match variables {
-                                Some(v) => {
-                                    for var in v {
-                                        variable_map.insert(var.variable.clone(), var.choice.clone());
-                                    }
-                                }
-                                None => {}
-                            }
-
-                            for srcom in &srcom_ids {
-                                match srcom_boards.fetch_user_highest_run(
-                                    *srcom,
-                                    *partner,
-                                    game.clone(),
-                                    category.clone(),
-                                    variable_map.clone()
-                                ).await? {
-                                    Some(run) => {
-                                        if run.run.times.primary_t <= seconds as f64 && run.place <= *top {
-                                            met_requirements.push(MetRequirement {
-                                                definition: requirement,
-                                                cause: fullgame_cause(srcom, &run)?
-                                            });
-                                            break;
-                                        }
-                                    }
-                                    None => {}
-                                }
-                            }
-                        }
-                    }
-                }
-                RequirementDefinition::Points { leaderboard, points } => {
-                    for steam_id in &steam_ids {
-                        let aggregate = cm_boards.fetch_aggregate(leaderboard).await?;
-                        let points_map = &aggregate.points;
-
-                        match points_map.get(&(steam_id.to_string())) {
-                            Some(place) => {
-                                if place.score_data.score >= *points as u32 {
-                                    met_requirements.push(MetRequirement {
-                                        definition: requirement,
-                                        cause: MetRequirementCause::CmAggregate {
-                                            steam_id: *steam_id,
-                                            board: *leaderboard,
-                                            points: place.score_data.score
-                                        }
-                                    });
-                                    break;
-                                }
-                            }
-                            None => {}
-                        }
-                    }
-                }
-                RequirementDefinition::Recent(recent) => {
-                    match recent {
-                        RecentRequirement::Srcom { game, category, variables, .. } => {
-                            let mut variable_map = BTreeMap::new();
-                            match variables {
-                                Some(v) => {
-                                    for var in v {
-                                        variable_map.insert(VariableId(var.variable.0.clone()), VariableValueId(var.choice.0.clone()));
-                                    }
-                                }
-                                None => {}
-                            }
-                            for srcom in &srcom_ids {
-                                match srcom_boards.fetch_user_highest_run(
-                                    *srcom,
-                                    None,
-                                    game.clone(),
-                                    category.clone(),
-                                    variable_map.clone()
-                                ).await? {
-                                    Some(run) => {
-                                        match &run.run.date {
-                                            Some(date_text) => {
-                                                if (Utc::now() - Duration::days(30 * 6)).timestamp() < speedate::Date::parse_str(date_text.as_str())
-                                                    .map_err(|err| RoleManagerError::new(format!("Speedrun.com provided invalid date: {} (Caused by {:?})", date_text, err)))?
-                                                    .timestamp() {
-                                                    met_requirements.push(MetRequirement {
-                                                        definition: requirement,
-                                                        cause: fullgame_cause(srcom, &run)?
-                                                    });
-                                                    break
-                                                }
-                                            }
-                                            None => {}
-                                        }
-                                    }
-                                    None => {}
-                                }
-                            }
-                        }
-                        RecentRequirement::Cm { months } => {
-                            let active_users = cm_boards.fetch_active_profiles(*months)
-                                .await?;
-
-                            for steam_id in &steam_ids {
-                                if active_users.contains(&steam_id.to_string()) {
-                                    met_requirements.push(MetRequirement {
-                                        definition: requirement,
-                                        cause: CmActivity {
-                                            steam_id: *steam_id
-                                        }
-                                    });
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-                RequirementDefinition::Manual => {}
+            match analyze_requirement(requirement, &badge_definition.name, &steam_ids, &srcom_ids, &srcom_boards, &cm_boards).await? {
+                Some(met) => met_requirements.push(met),
+                None => unmet_requirements.push(UnmetRequirement {
+                    definition: requirement,
+                    gap: None,
+                    branches: Vec::new()
+                })
             }
         }
 
-        if met_requirements.len() > 0 {
-            analyzed_badges.push(AnalyzedUserBadge {
-                definition: badge_definition,
-                met_requirements
-            });
+        let analyzed_badge = AnalyzedUserBadge {
+            definition: badge_definition,
+            met_requirements,
+            unmet_requirements
+        };
+        if analyzed_badge.met_requirements.len() > 0 {
+            analyzed_badges.push(analyzed_badge);
+        } else {
+            unearned_badges.push(analyzed_badge);
         }
     }
 
@@ -415,6 +501,445 @@ pub async fn analyze_user<'a>(
     Ok(AnalyzedUser {
         discord_id,
         external_accounts,
-        badges: analyzed_badges
+        badges: analyzed_badges,
+        unearned_badges
+    })
+}
+
+async fn analyze_requirement<'a>(
+    requirement: &'a RequirementDefinition,
+    badge_name: &str,
+    steam_ids: &[i64],
+    srcom_ids: &[UserId],
+    srcom_boards: &SrComBoardsState,
+    cm_boards: &CmBoardsState
+) -> Result<Option<MetRequirement<'a>>, RoleManagerError> {
+    match requirement {
+        RequirementDefinition::All { .. } | RequirementDefinition::Any { .. } | RequirementDefinition::AtLeast { .. } => {
+            let (of, needed) = requirement.group().unwrap();
+
+            let mut branches = Vec::new();
+            for nested in of {
+                if let Some(met) = Box::pin(analyze_requirement(nested, badge_name, steam_ids, srcom_ids, srcom_boards, cm_boards)).await? {
+                    branches.push(met);
+                }
+            }
+
+            if branches.len() >= needed {
+                return Ok(Some(MetRequirement {
+                    definition: requirement,
+                    cause: MetRequirementCause::Group {
+                        met: branches.len(),
+                        needed,
+                        total: of.len()
+                    },
+                    branches
+                }));
+            }
+        }
+        RequirementDefinition::Rank(req) => {
+            match req {
+                RankRequirement::Srcom { game, category, level, variables, filters, top, partner } => {
+                    let timing = compared_timing(&None, filters);
+                    let board = srcom_board(game, category, level, variables, filters);
+
+                    for srcom in srcom_ids {
+                        match srcom_boards.fetch_user_highest_run(*srcom, *partner, board.clone()).await? {
+                            Some(run) => {
+                                if run.place <= *top {
+                                    return Ok(Some(MetRequirement {
+                                        definition: requirement,
+                                        cause: fullgame_cause(srcom, &run, timing)?,
+                                        branches: Vec::new()
+                                    }));
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        RequirementDefinition::Time(req) => {
+            match req {
+                TimeRequirement::Srcom { game, category, level, variables, filters, time, timing, partner } => {
+                    let timing = compared_timing(timing, filters);
+                    let seconds = speedate::Duration::parse_str(time.as_str())
+                        .map_err(|err| RoleManagerError::new(format!("Invalid duration specified in badge {}, {} (caused by {:?})", badge_name, time, err)))?
+                        .signed_total_seconds();
+
+                    let board = srcom_board(game, category, level, variables, filters);
+
+                    for srcom in srcom_ids {
+                        match srcom_boards.fetch_user_highest_run(*srcom, *partner, board.clone()).await? {
+                            Some(run) => {
+                                if run.run.times.seconds(timing).is_some_and(|run_seconds| run_seconds <= seconds as f64) {
+                                    return Ok(Some(MetRequirement {
+                                        definition: requirement,
+                                        cause: fullgame_cause(srcom, &run, timing)?,
+                                        branches: Vec::new()
+                                    }));
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        RequirementDefinition::RankTime(req) => {
+            match req {
+                RankTimeRequirement::Srcom { game, category, level, variables, filters, time, timing, top, partner } => {
+                    let timing = compared_timing(timing, filters);
+                    let seconds = speedate::Duration::parse_str(time.as_str())
+                        .map_err(|err| RoleManagerError::new(format!("Invalid duration specified in badge {}, {} (caused by {:?})", badge_name, time, err)))?
+                        .signed_total_seconds();
+
+                    let board = srcom_board(game, category, level, variables, filters);
+
+                    for srcom in srcom_ids {
+                        match srcom_boards.fetch_user_highest_run(*srcom, *partner, board.clone()).await? {
+                            Some(run) => {
+                                if run.run.times.seconds(timing).is_some_and(|run_seconds| run_seconds <= seconds as f64) && run.place <= *top {
+                                    return Ok(Some(MetRequirement {
+                                        definition: requirement,
+                                        cause: fullgame_cause(srcom, &run, timing)?,
+                                        branches: Vec::new()
+                                    }));
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        RequirementDefinition::Points { leaderboard, points } => {
+            for steam_id in steam_ids {
+                let aggregate = cm_boards.fetch_aggregate(leaderboard).await?;
+                let points_map = &aggregate.points;
+
+                match points_map.get(&(steam_id.to_string())) {
+                    Some(place) => {
+                        if place.score_data.score >= *points as u32 {
+                            return Ok(Some(MetRequirement {
+                                definition: requirement,
+                                cause: MetRequirementCause::CmAggregate {
+                                    steam_id: *steam_id,
+                                    board: *leaderboard,
+                                    points: place.score_data.score
+                                },
+                                branches: Vec::new()
+                            }));
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        RequirementDefinition::Recent(recent) => {
+            match recent {
+                RecentRequirement::Srcom { game, category, level, variables, filters, months } => {
+                    let since = months_ago(*months);
+                    let board = srcom_board(game, category, level, variables, filters);
+
+                    if let Some((srcom, run, date)) = most_recent_srcom_run(srcom_ids, board, Some(since), srcom_boards).await?
+                        && date >= since {
+                        return Ok(Some(MetRequirement {
+                            definition: requirement,
+                            cause: MetRequirementCause::RecentRun {
+                                srcom_id: srcom,
+                                link: run.weblink.clone(),
+                                time: format_run_time(run.times.primary_t),
+                                achieved_on: date
+                            },
+                            branches: Vec::new()
+                        }));
+                    }
+                }
+                RecentRequirement::Cm { months } => {
+                    let active_users = cm_boards.fetch_active_profiles(*months)
+                        .await?;
+
+                    for steam_id in steam_ids {
+                        if active_users.contains(&steam_id.to_string()) {
+                            return Ok(Some(MetRequirement {
+                                definition: requirement,
+                                cause: CmActivity {
+                                    steam_id: *steam_id
+                                },
+                                branches: Vec::new()
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        RequirementDefinition::CmChamber(req) => {
+            let centiseconds = match &req.time {
+                Some(time) => {
+                    let duration = speedate::Duration::parse_str(time.as_str())
+                        .map_err(|err| RoleManagerError::new(format!("Invalid duration specified in badge {}, {} (caused by {:?})", badge_name, time, err)))?;
+                    Some(duration.signed_total_seconds() * 100 + (duration.signed_microseconds() / 10_000) as i64)
+                }
+                None => None
+            };
+
+            let chamber = cm_boards.fetch_chamber(req.chamber).await?;
+
+            for steam_id in steam_ids {
+                if let Some(place) = chamber.points.get(&steam_id.to_string()) {
+                    let meets_top = req.top.is_none_or(|top| place.score_data.player_rank as u64 <= top);
+                    let meets_time = centiseconds.is_none_or(|limit| place.score_data.score as i64 <= limit);
+
+                    if meets_top && meets_time {
+                        return Ok(Some(MetRequirement {
+                            definition: requirement,
+                            cause: cm_run_cause(*steam_id, req, place)?,
+                            branches: Vec::new()
+                        }));
+                    }
+                }
+            }
+        }
+        RequirementDefinition::Manual => {}
+    }
+
+    Ok(None)
+}
+
+/// Finds the best placed run across all of the user's speedrun.com accounts
+async fn best_srcom_run(
+    srcom_ids: &[UserId],
+    partner: Option<PartnerRestriction>,
+    board: BoardDefinition,
+    srcom_boards: &SrComBoardsState
+) -> Result<Option<Arc<LeaderboardPlace>>, RoleManagerError> {
+    let mut best: Option<Arc<LeaderboardPlace>> = None;
+    for srcom in srcom_ids {
+        if let Some(run) = srcom_boards.fetch_user_highest_run(*srcom, partner, board.clone()).await? && best.as_ref().is_none_or(|best| run.place < best.place) {
+            best = Some(run);
+        }
+    }
+
+    Ok(best)
+}
+
+/// Finds the most recent verified run across all of the user's speedrun.com accounts, including obsolete runs.
+/// A leaderboard run done on or after `since` is taken as it is, so the runs endpoint is only asked about accounts
+/// whose leaderboard run is older.
+async fn most_recent_srcom_run(
+    srcom_ids: &[UserId],
+    board: BoardDefinition,
+    since: Option<NaiveDate>,
+    srcom_boards: &SrComBoardsState
+) -> Result<Option<(UserId, Run, NaiveDate)>, RoleManagerError> {
+    let mut most_recent: Option<(UserId, Run, NaiveDate)> = None;
+    for srcom in srcom_ids {
+        // Without a run on the leaderboard, the user has no verified runs on the board at all
+        let place = match srcom_boards.fetch_user_highest_run(*srcom, None, board.clone()).await? {
+            Some(place) => place,
+            None => continue
+        };
+
+        let leaderboard_date = run_date(&place.run)?;
+        let (run, date) = match (leaderboard_date, since) {
+            (Some(date), Some(since)) if date >= since => return Ok(Some((*srcom, place.run.clone(), date))),
+            _ => match srcom_boards.fetch_latest_user_run(*srcom, board.clone()).await? {
+                Some(run) => match run_date(&run)? {
+                    Some(date) => (run.as_ref().clone(), date),
+                    None => continue
+                },
+                None => continue
+            }
+        };
+
+        if most_recent.as_ref().is_none_or(|(_, _, latest)| date > *latest) {
+            most_recent = Some((*srcom, run, date));
+        }
+    }
+
+    Ok(most_recent)
+}
+
+/// The day a speedrun.com run was done, if it was given
+fn run_date(run: &Run) -> Result<Option<NaiveDate>, RoleManagerError> {
+    run.date.as_ref()
+        .map(|date| NaiveDate::parse_from_str(date.as_str(), "%Y-%m-%d")
+            .map_err(|err| RoleManagerError::new(format!("Speedrun.com provided invalid date: {} (Caused by {})", date, err))))
+        .transpose()
+}
+
+/// The first day which counts as within the last `months` months
+fn months_ago(months: u64) -> NaiveDate {
+    let today = Utc::now().date_naive();
+    u32::try_from(months).ok()
+        .and_then(|months| today.checked_sub_months(Months::new(months)))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+/// Works out what is missing for a requirement which analyze_requirement didn't find met, along with the unmet
+/// branches of requirement groups
+async fn unmet_requirement<'a>(
+    requirement: &'a RequirementDefinition,
+    badge_name: &str,
+    steam_ids: &[i64],
+    srcom_ids: &[UserId],
+    srcom_boards: &SrComBoardsState,
+    cm_boards: &CmBoardsState
+) -> Result<UnmetRequirement<'a>, RoleManagerError> {
+    let (of, needed) = match requirement.group() {
+        Some(group) => group,
+        None => return Ok(UnmetRequirement {
+            definition: requirement,
+            gap: Some(requirement_gap(requirement, steam_ids, srcom_ids, srcom_boards, cm_boards).await?),
+            branches: Vec::new()
+        })
+    };
+
+    let mut met = 0;
+    let mut branches = Vec::new();
+    for nested in of {
+        if analyze_requirement(nested, badge_name, steam_ids, srcom_ids, srcom_boards, cm_boards).await?.is_some() {
+            met += 1;
+        } else {
+            branches.push(Box::pin(unmet_requirement(nested, badge_name, steam_ids, srcom_ids, srcom_boards, cm_boards)).await?);
+        }
+    }
+
+    Ok(UnmetRequirement {
+        definition: requirement,
+        gap: Some(RequirementGap::Group { met, needed, total: of.len() }),
+        branches
     })
 }
+
+/// Works out how far the user is from meeting a single requirement which analyze_requirement didn't find met
+async fn requirement_gap(
+    requirement: &RequirementDefinition,
+    steam_ids: &[i64],
+    srcom_ids: &[UserId],
+    srcom_boards: &SrComBoardsState,
+    cm_boards: &CmBoardsState
+) -> Result<RequirementGap, RoleManagerError> {
+    let no_srcom = RequirementGap::NoAccount { platform: Platform::Srcom };
+    let no_steam = RequirementGap::NoAccount { platform: Platform::Cm };
+
+    let gap = match requirement {
+        // Groups are broken down into their branches by unmet_requirement
+        RequirementDefinition::All { .. } | RequirementDefinition::Any { .. } | RequirementDefinition::AtLeast { .. } => {
+            let (of, needed) = requirement.group().unwrap();
+            RequirementGap::Group { met: 0, needed, total: of.len() }
+        }
+        RequirementDefinition::Rank(RankRequirement::Srcom { game, category, level, variables, filters, partner, top }) => {
+            if srcom_ids.is_empty() {
+                return Ok(no_srcom);
+            }
+
+            match best_srcom_run(srcom_ids, *partner, srcom_board(game, category, level, variables, filters), srcom_boards).await? {
+                Some(run) => RequirementGap::Rank { rank: run.place, needed: *top },
+                None => RequirementGap::NoRun
+            }
+        }
+        RequirementDefinition::Time(TimeRequirement::Srcom { game, category, level, variables, filters, partner, time, timing }) => {
+            let timing = compared_timing(timing, filters);
+            if srcom_ids.is_empty() {
+                return Ok(no_srcom);
+            }
+
+            match best_srcom_run(srcom_ids, *partner, srcom_board(game, category, level, variables, filters), srcom_boards).await? {
+                Some(run) => match run.run.times.seconds(timing) {
+                    Some(seconds) => RequirementGap::Time {
+                        time: format_run_time(seconds),
+                        needed: time.clone()
+                    },
+                    // Only a chosen timing method can be missing from a run
+                    None => RequirementGap::NoTiming { timing: timing.unwrap() }
+                },
+                None => RequirementGap::NoRun
+            }
+        }
+        RequirementDefinition::RankTime(RankTimeRequirement::Srcom { game, category, level, variables, filters, partner, time, timing, top }) => {
+            let timing = compared_timing(timing, filters);
+            if srcom_ids.is_empty() {
+                return Ok(no_srcom);
+            }
+
+            match best_srcom_run(srcom_ids, *partner, srcom_board(game, category, level, variables, filters), srcom_boards).await? {
+                Some(run) => match run.run.times.seconds(timing) {
+                    Some(seconds) => RequirementGap::RankTime {
+                        rank: run.place,
+                        time: format_run_time(seconds),
+                        needed_rank: *top,
+                        needed_time: time.clone()
+                    },
+                    // Only a chosen timing method can be missing from a run
+                    None => RequirementGap::NoTiming { timing: timing.unwrap() }
+                },
+                None => RequirementGap::NoRun
+            }
+        }
+        RequirementDefinition::Points { leaderboard, points } => {
+            if steam_ids.is_empty() {
+                return Ok(no_steam);
+            }
+
+            let aggregate = cm_boards.fetch_aggregate(leaderboard).await?;
+            let best = steam_ids.iter()
+                .filter_map(|steam_id| aggregate.points.get(&steam_id.to_string()))
+                .map(|place| place.score_data.score as u64)
+                .max()
+                .unwrap_or(0);
+
+            RequirementGap::Points { points: best, needed: *points }
+        }
+        RequirementDefinition::Recent(RecentRequirement::Srcom { game, category, level, variables, filters, .. }) => {
+            if srcom_ids.is_empty() {
+                return Ok(no_srcom);
+            }
+
+            match most_recent_srcom_run(srcom_ids, srcom_board(game, category, level, variables, filters), None, srcom_boards).await? {
+                Some((_, _, date)) => RequirementGap::LastRun { date: date.to_string() },
+                None => RequirementGap::NoRun
+            }
+        }
+        RequirementDefinition::Recent(RecentRequirement::Cm { months }) => {
+            if steam_ids.is_empty() {
+                return Ok(no_steam);
+            }
+
+            RequirementGap::Inactive { months: *months }
+        }
+        RequirementDefinition::CmChamber(req) => {
+            if steam_ids.is_empty() {
+                return Ok(no_steam);
+            }
+
+            let chamber = cm_boards.fetch_chamber(req.chamber).await?;
+            let best = steam_ids.iter()
+                .filter_map(|steam_id| chamber.points.get(&steam_id.to_string()))
+                .min_by_key(|place| place.score_data.player_rank);
+
+            match (best, req.top, &req.time) {
+                (None, _, _) | (Some(_), None, None) => RequirementGap::NoRun,
+                (Some(place), Some(top), None) => RequirementGap::Rank {
+                    rank: place.score_data.player_rank as u64,
+                    needed: top
+                },
+                (Some(place), None, Some(time)) => RequirementGap::Time {
+                    time: format_cm_time(place.score_data.score),
+                    needed: time.clone()
+                },
+                (Some(place), Some(top), Some(time)) => RequirementGap::RankTime {
+                    rank: place.score_data.player_rank as u64,
+                    time: format_cm_time(place.score_data.score),
+                    needed_rank: top,
+                    needed_time: time.clone()
+                }
+            }
+        }
+        RequirementDefinition::Manual => RequirementGap::Manual
+    };
+
+    Ok(gap)
+}
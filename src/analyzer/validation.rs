@@ -0,0 +1,331 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use chrono::NaiveDate;
+use crate::analyzer::role_definition::{CmChamberRequirement, RankRequirement, RankTimeRequirement, RecentRequirement, RequirementDefinition, RoleDefinition, TimeRequirement, VariableDefinition};
+use crate::boards::srcom::{SrComBoardsState, TimingMethod};
+use crate::boards::srcom::leaderboard::BoardFilters;
+use crate::boards::srcom::category::CategoryId;
+use crate::boards::srcom::game::GameId;
+use crate::boards::srcom::level::LevelId;
+
+#[derive(Debug)]
+pub struct ValidationReport {
+    /// Problems with the definition as a whole, such as duplicate badge names
+    pub problems: Vec<String>,
+    pub badges: Vec<BadgeValidation>
+}
+
+#[derive(Debug)]
+pub struct BadgeValidation {
+    pub name: String,
+    pub problems: Vec<String>,
+    pub requirements: Vec<RequirementValidation>
+}
+
+#[derive(Debug)]
+pub struct RequirementValidation {
+    /// The requirement with every id resolved to its name, or its short description if that failed
+    pub preview: String,
+    pub problems: Vec<String>,
+    pub nested: Vec<RequirementValidation>
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problem_count() == 0
+    }
+
+    pub fn problem_count(&self) -> usize {
+        self.problems.len() + self.badges.iter()
+            .map(|badge| badge.problems.len() + badge.requirements.iter().map(|req| req.problem_count()).sum::<usize>())
+            .sum::<usize>()
+    }
+
+    pub fn requirement_count(&self) -> usize {
+        self.badges.iter()
+            .map(|badge| badge.requirements.len())
+            .sum()
+    }
+}
+
+impl RequirementValidation {
+    pub fn problem_count(&self) -> usize {
+        self.problems.len() + self.nested.iter().map(|req| req.problem_count()).sum::<usize>()
+    }
+
+    fn write_indented(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth);
+        let marker = if self.problem_count() == 0 { "ok" } else { "!!" };
+
+        writeln!(f, "{}[{}] {}", indent, marker, self.preview)?;
+        for problem in &self.problems {
+            writeln!(f, "{}     - {}", indent, problem)?;
+        }
+        for nested in &self.nested {
+            nested.write_indented(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for problem in &self.problems {
+            writeln!(f, "[!!] {}", problem)?;
+        }
+
+        for badge in &self.badges {
+            writeln!(f, "\nBadge: {}", badge.name)?;
+            for problem in &badge.problems {
+                writeln!(f, "  [!!] {}", problem)?;
+            }
+            for requirement in &badge.requirements {
+                requirement.write_indented(f, 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RoleDefinition {
+    /// Resolves every speedrun.com id referenced by this definition and checks it for mistakes which would
+    /// otherwise only surface while analyzing users
+    pub async fn validate(&self, srcom_state: &SrComBoardsState) -> ValidationReport {
+        let mut problems = Vec::new();
+
+        let mut seen_names = HashSet::new();
+        for badge in &self.badges {
+            if !seen_names.insert(&badge.name) {
+                problems.push(format!("Badge name `{}` is used more than once", badge.name));
+            }
+        }
+
+        let mut badges = Vec::new();
+        for badge in &self.badges {
+            let mut badge_problems = Vec::new();
+            if badge.requirements.is_empty() {
+                badge_problems.push("Badge has no requirements".to_string());
+            }
+
+            let mut requirements = Vec::new();
+            for requirement in &badge.requirements {
+                requirements.push(validate_requirement(requirement, srcom_state).await);
+            }
+
+            badges.push(BadgeValidation {
+                name: badge.name.clone(),
+                problems: badge_problems,
+                requirements
+            });
+        }
+
+        ValidationReport {
+            problems,
+            badges
+        }
+    }
+}
+
+async fn validate_requirement(requirement: &RequirementDefinition, srcom_state: &SrComBoardsState) -> RequirementValidation {
+    let mut problems = Vec::new();
+    let mut nested = Vec::new();
+
+    match requirement {
+        RequirementDefinition::Rank(RankRequirement::Srcom { game, category, level, variables, filters, top, .. }) => {
+            validate_srcom_board(srcom_state, game, category, level, variables, &mut problems).await;
+            validate_filters(srcom_state, game, filters, &mut problems).await;
+            validate_top(*top, &mut problems);
+        }
+        RequirementDefinition::Time(TimeRequirement::Srcom { game, category, level, variables, filters, time, timing, .. }) => {
+            validate_srcom_board(srcom_state, game, category, level, variables, &mut problems).await;
+            validate_filters(srcom_state, game, filters, &mut problems).await;
+            validate_duration(time, &mut problems);
+            validate_timing(srcom_state, game, timing, &mut problems).await;
+            validate_timing_conflict(filters, timing, &mut problems);
+        }
+        RequirementDefinition::RankTime(RankTimeRequirement::Srcom { game, category, level, variables, filters, time, timing, top, .. }) => {
+            validate_srcom_board(srcom_state, game, category, level, variables, &mut problems).await;
+            validate_filters(srcom_state, game, filters, &mut problems).await;
+            validate_duration(time, &mut problems);
+            validate_timing(srcom_state, game, timing, &mut problems).await;
+            validate_timing_conflict(filters, timing, &mut problems);
+            validate_top(*top, &mut problems);
+        }
+        RequirementDefinition::Recent(RecentRequirement::Srcom { game, category, level, variables, filters, months }) => {
+            validate_srcom_board(srcom_state, game, category, level, variables, &mut problems).await;
+            validate_filters(srcom_state, game, filters, &mut problems).await;
+            validate_months(*months, &mut problems);
+        }
+        RequirementDefinition::Recent(RecentRequirement::Cm { months }) => {
+            validate_months(*months, &mut problems);
+        }
+        RequirementDefinition::CmChamber(CmChamberRequirement { time, top, .. }) => {
+            if let Some(time) = time {
+                validate_duration(time, &mut problems);
+            }
+            if let Some(top) = top {
+                validate_top(*top, &mut problems);
+            }
+        }
+        RequirementDefinition::All { .. } | RequirementDefinition::Any { .. } | RequirementDefinition::AtLeast { .. } => {
+            let (of, _) = requirement.group().unwrap();
+
+            if of.is_empty() {
+                problems.push("Requirement group is empty".to_string());
+            } else if let RequirementDefinition::AtLeast { n, .. } = requirement {
+                validate_at_least(*n, of.len(), &mut problems);
+            }
+
+            for req in of {
+                nested.push(Box::pin(validate_requirement(req, srcom_state)).await);
+            }
+        }
+        RequirementDefinition::Manual | RequirementDefinition::Points { .. } => {}
+    }
+
+    // Only try to resolve names for the preview if the ids could be resolved
+    let preview = if problems.is_empty() {
+        match requirement.format(srcom_state.clone()).await {
+            Ok(preview) => preview,
+            Err(err) => {
+                problems.push(err.cause);
+                requirement.short_description()
+            }
+        }
+    } else {
+        requirement.short_description()
+    };
+
+    RequirementValidation {
+        preview,
+        problems,
+        nested
+    }
+}
+
+async fn validate_srcom_board(
+    srcom_state: &SrComBoardsState,
+    game: &GameId,
+    category: &CategoryId,
+    level: &Option<LevelId>,
+    variables: &Option<Vec<VariableDefinition>>,
+    problems: &mut Vec<String>
+) {
+    if let Err(err) = srcom_state.fetch_game(game.clone()).await {
+        problems.push(format!("Game `{}` could not be resolved: {}", game.0, err.cause));
+    }
+
+    match srcom_state.fetch_category(category.clone()).await {
+        Ok(resolved) => {
+            let belongs_to_game = resolved.links.iter()
+                .flatten()
+                .filter(|link| link.rel == "game")
+                .all(|link| link.uri.ends_with(&format!("/{}", game.0)));
+
+            if !belongs_to_game {
+                problems.push(format!("Category `{}` ({}) does not belong to game `{}`", category.0, resolved.name, game.0));
+            }
+        }
+        Err(err) => problems.push(format!("Category `{}` could not be resolved: {}", category.0, err.cause))
+    }
+
+    if let Some(level) = level
+        && let Err(err) = srcom_state.fetch_level(level.clone()).await {
+        problems.push(format!("Level `{}` could not be resolved: {}", level.0, err.cause));
+    }
+
+    for id_pair in variables.iter().flatten() {
+        match srcom_state.fetch_variable(id_pair.variable.clone()).await {
+            Ok(variable) => {
+                if !variable.values.values.contains_key(&id_pair.choice) {
+                    problems.push(format!("Choice `{}` is not a value of variable `{}` ({})", id_pair.choice.0, id_pair.variable.0, variable.name));
+                }
+
+                if let Some(variable_category) = &variable.category
+                    && variable_category != category {
+                    problems.push(format!("Variable `{}` ({}) belongs to category `{}`, not `{}`", id_pair.variable.0, variable.name, variable_category.0, category.0));
+                }
+            }
+            Err(err) => problems.push(format!("Variable `{}` could not be resolved: {}", id_pair.variable.0, err.cause))
+        }
+    }
+}
+
+/// Checks a board's filters could match any runs of the game
+async fn validate_filters(srcom_state: &SrComBoardsState, game: &GameId, filters: &Option<BoardFilters>, problems: &mut Vec<String>) {
+    let filters = match filters {
+        Some(filters) => filters,
+        None => return
+    };
+
+    if let Some(date) = &filters.date
+        && NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+        problems.push(format!("Invalid date `{}`, expected YYYY-MM-DD", date));
+    }
+
+    validate_timing(srcom_state, game, &filters.timing, problems).await;
+
+    // A game which can't be resolved is already reported by validate_srcom_board
+    if let Ok(resolved) = srcom_state.fetch_game(game.clone()).await {
+        if let Some(platform) = &filters.platform
+            && !resolved.platforms.contains(&platform.0) {
+            problems.push(format!("Platform `{}` is not a platform of game `{}` ({})", platform.0, game.0, resolved.names.international));
+        }
+        if let Some(region) = &filters.region
+            && !resolved.regions.contains(&region.0) {
+            problems.push(format!("Region `{}` is not a region of game `{}` ({})", region.0, game.0, resolved.names.international));
+        }
+    }
+}
+
+/// Checks the game actually times runs in the requirement's timing method
+async fn validate_timing(srcom_state: &SrComBoardsState, game: &GameId, timing: &Option<TimingMethod>, problems: &mut Vec<String>) {
+    let timing = match timing {
+        Some(timing) => timing,
+        None => return
+    };
+
+    // A game which can't be resolved is already reported by validate_srcom_board
+    if let Ok(resolved) = srcom_state.fetch_game(game.clone()).await
+        && !resolved.ruleset.run_times.contains(timing) {
+        let run_times: Vec<String> = resolved.ruleset.run_times.iter().map(|run_time| run_time.to_string()).collect();
+        problems.push(format!("Game `{}` ({}) doesn't time runs in {}, only in {}", game.0, resolved.names.international, timing, run_times.join(", ")));
+    }
+}
+
+/// Checks a requirement doesn't compare times in a different timing method than its filters rank the board by
+fn validate_timing_conflict(filters: &Option<BoardFilters>, timing: &Option<TimingMethod>, problems: &mut Vec<String>) {
+    if let Some(filter_timing) = filters.as_ref().and_then(|filters| filters.timing)
+        && let Some(timing) = timing
+        && filter_timing != *timing {
+        problems.push(format!("Board is ranked by {} but the time is compared in {}, set only one of them", filter_timing, timing));
+    }
+}
+
+fn validate_duration(time: &str, problems: &mut Vec<String>) {
+    if let Err(err) = speedate::Duration::parse_str(time) {
+        problems.push(format!("Invalid duration `{}` (caused by {:?})", time, err));
+    }
+}
+
+fn validate_top(top: u64, problems: &mut Vec<String>) {
+    if top == 0 {
+        problems.push("`top` must be at least 1".to_string());
+    }
+}
+
+fn validate_at_least(n: u64, total: usize, problems: &mut Vec<String>) {
+    if n == 0 {
+        problems.push("`n` must be at least 1, otherwise the group is always met".to_string());
+    } else if n > total as u64 {
+        problems.push(format!("`n` is {} but the group only has {} requirements, so it can never be met", n, total));
+    }
+}
+
+fn validate_months(months: u64, problems: &mut Vec<String>) {
+    if months == 0 {
+        problems.push("`months` must be at least 1".to_string());
+    }
+}
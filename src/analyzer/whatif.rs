@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use crate::analyzer::role_definition::{BadgeDefinition, CmChamberRequirement, RankRequirement, RankTimeRequirement, RequirementDefinition, RoleDefinition, TimeRequirement};
+use crate::analyzer::user;
+use crate::boards::cm::CmBoardsState;
+use crate::boards::srcom::SrComBoardsState;
+use crate::error::RoleManagerError;
+use crate::model::lumadb::verified_connections;
+
+/// The numeric part of a requirement which can be swept
+#[derive(Debug, Clone, Copy, Eq, PartialEq, poise::ChoiceParameter)]
+pub enum ThresholdParameter {
+    #[name = "points"]
+    Points,
+    #[name = "top"]
+    Top,
+    #[name = "time"]
+    Time
+}
+
+impl ThresholdParameter {
+    /// The only threshold a requirement has, if it isn't ambiguous
+    pub fn default_for(requirement: &RequirementDefinition) -> Option<Self> {
+        match requirement {
+            RequirementDefinition::Points { .. } => Some(Self::Points),
+            RequirementDefinition::Rank(_) => Some(Self::Top),
+            RequirementDefinition::Time(_) => Some(Self::Time),
+            RequirementDefinition::CmChamber(CmChamberRequirement { top: Some(_), time: None, .. }) => Some(Self::Top),
+            RequirementDefinition::CmChamber(CmChamberRequirement { top: None, time: Some(_), .. }) => Some(Self::Time),
+            _ => None
+        }
+    }
+
+    /// Parses a threshold value. Times are kept in milliseconds.
+    fn parse_value(&self, text: &str) -> Result<u64, RoleManagerError> {
+        match self {
+            Self::Points | Self::Top => text.trim().parse()
+                .map_err(|err| RoleManagerError::new(format!("Invalid threshold {} (caused by {})", text, err))),
+            Self::Time => {
+                let duration = speedate::Duration::parse_str(text.trim())
+                    .map_err(|err| RoleManagerError::new(format!("Invalid duration {} (caused by {:?})", text, err)))?;
+                Ok(duration.signed_total_seconds().max(0) as u64 * 1_000 + duration.microsecond as u64 / 1_000)
+            }
+        }
+    }
+
+    fn format_value(&self, value: u64) -> String {
+        match self {
+            Self::Points | Self::Top => value.to_string(),
+            Self::Time => {
+                let seconds = value / 1_000;
+                let time = format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+                match value % 1_000 {
+                    0 => time,
+                    milliseconds => format!("{}.{:03}", time, milliseconds)
+                }
+            }
+        }
+    }
+}
+
+/// Evenly spaced values for a threshold, from one end of a range to the other
+#[derive(Debug, Clone)]
+pub struct ThresholdSweep {
+    pub parameter: ThresholdParameter,
+    values: Vec<u64>
+}
+
+impl ThresholdSweep {
+    pub fn new(parameter: ThresholdParameter, from: &str, to: &str, steps: u64) -> Result<Self, RoleManagerError> {
+        if steps < 2 {
+            return Err(RoleManagerError::new("A sweep needs at least 2 steps".to_string()));
+        }
+
+        let from = parameter.parse_value(from)?;
+        let to = parameter.parse_value(to)?;
+
+        let mut values: Vec<u64> = (0..steps)
+            .map(|step| (from as f64 + (to as f64 - from as f64) * step as f64 / (steps - 1) as f64).round() as u64)
+            .collect();
+        values.dedup();
+
+        Ok(ThresholdSweep {
+            parameter,
+            values
+        })
+    }
+
+    /// Each threshold of the sweep, formatted as it would be written in a definition
+    pub fn thresholds(&self) -> Vec<String> {
+        self.values.iter().map(|value| self.parameter.format_value(*value)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SweepStep {
+    pub threshold: String,
+    pub qualifying: u64
+}
+
+/// Counts how many of the given users would meet a requirement at each threshold of a sweep.
+/// Every threshold is checked in the same analysis, so each leaderboard is only fetched once.
+pub async fn sweep_requirement(requirement: &RequirementDefinition,
+                               sweep: &ThresholdSweep,
+                               user_ids: &[u64],
+                               connections: &[verified_connections::Model],
+                               srcom_state: SrComBoardsState,
+                               cm_state: CmBoardsState) -> Result<Vec<SweepStep>, RoleManagerError> {
+    let thresholds = sweep.thresholds();
+
+    // Each step of the sweep becomes a badge with just the adjusted requirement
+    let mut badges = Vec::new();
+    for (i, value) in sweep.values.iter().enumerate() {
+        badges.push(BadgeDefinition {
+            name: i.to_string(),
+            requirements: vec![with_threshold(requirement, sweep.parameter, *value)?]
+        });
+    }
+    let definition = RoleDefinition { badges };
+
+    let mut connections_by_user: HashMap<i64, Vec<verified_connections::Model>> = HashMap::new();
+    for connection in connections {
+        connections_by_user.entry(connection.user_id).or_default().push(connection.clone());
+    }
+
+    let mut counts = vec![0; thresholds.len()];
+    for user_id in user_ids {
+        let user_connections = match connections_by_user.get(&(*user_id as i64)) {
+            Some(user_connections) => user_connections,
+            None => continue
+        };
+
+        let analysis = user::analyze_user(*user_id, &definition, user_connections, srcom_state.clone(), cm_state.clone(), false).await?;
+        for badge in &analysis.badges {
+            if let Ok(step) = badge.definition.name.parse::<usize>() {
+                counts[step] += 1;
+            }
+        }
+    }
+
+    Ok(thresholds.into_iter()
+        .zip(counts)
+        .map(|(threshold, qualifying)| SweepStep { threshold, qualifying })
+        .collect())
+}
+
+/// Copies a requirement with one of its thresholds replaced
+fn with_threshold(requirement: &RequirementDefinition, parameter: ThresholdParameter, value: u64) -> Result<RequirementDefinition, RoleManagerError> {
+    let mut adjusted = requirement.clone();
+
+    let replaced = match (&mut adjusted, parameter) {
+        (RequirementDefinition::Points { points, .. }, ThresholdParameter::Points) => {
+            *points = value;
+            true
+        }
+        (RequirementDefinition::Rank(RankRequirement::Srcom { top, .. }), ThresholdParameter::Top)
+        | (RequirementDefinition::RankTime(RankTimeRequirement::Srcom { top, .. }), ThresholdParameter::Top) => {
+            *top = value;
+            true
+        }
+        (RequirementDefinition::CmChamber(CmChamberRequirement { top, .. }), ThresholdParameter::Top) => {
+            *top = Some(value);
+            true
+        }
+        (RequirementDefinition::Time(TimeRequirement::Srcom { time, .. }), ThresholdParameter::Time)
+        | (RequirementDefinition::RankTime(RankTimeRequirement::Srcom { time, .. }), ThresholdParameter::Time) => {
+            *time = parameter.format_value(value);
+            true
+        }
+        (RequirementDefinition::CmChamber(CmChamberRequirement { time, .. }), ThresholdParameter::Time) => {
+            *time = Some(parameter.format_value(value));
+            true
+        }
+        _ => false
+    };
+
+    if replaced {
+        Ok(adjusted)
+    } else {
+        Err(RoleManagerError::new(format!("Requirement {} has no {:?} threshold to sweep", requirement.short_description(), parameter)))
+    }
+}
@@ -0,0 +1,228 @@
+//! Runs analyses from a terminal against a definition file and a file of connections, without Discord or a database
+
+use std::process::exit;
+use std::sync::Arc;
+use chrono::Duration;
+use serde::Deserialize;
+use role_manager::analyzer;
+use role_manager::analyzer::compare::compare_definitions;
+use role_manager::analyzer::pipeline::DEFAULT_ANALYSIS_WORKERS;
+use role_manager::analyzer::role_definition::RoleDefinition;
+use role_manager::analyzer::user::{analyze_user, ExternalAccount};
+use role_manager::boards::cache::{BoardCache, FileBoardCache};
+use role_manager::boards::cm::CmBoardsState;
+use role_manager::boards::srcom::SrComBoardsState;
+use role_manager::error::RoleManagerError;
+use role_manager::model::lumadb::verified_connections;
+use role_manager::store::ConnectionStore;
+use role_manager::store::memory::MemoryStore;
+
+const USAGE: &str = "Usage:
+  role-manager-cli users <definition.json5> <connections> [discord id]
+  role-manager-cli summary <definition.json5> <connections>
+  role-manager-cli report <definition.json5> <connections> <badge> [output.csv]
+  role-manager-cli compare <old.json5> <new.json5> <connections> [output.csv]
+
+<connections> is a CSV file with the columns user_id,type,id and optionally user_name,
+or a JSON5 file holding an array of objects with the same fields. type is steam or srcom.
+
+Set ROLE_MANAGER_BOARD_CACHE to a directory to keep downloaded boards between runs.";
+
+/// A linked account, along with the name used for its discord user in reports
+#[derive(Deserialize, Debug)]
+struct ConnectionRow {
+    user_id: u64,
+    #[serde(rename = "type")]
+    connection_type: String,
+    id: String,
+    #[serde(default)]
+    user_name: Option<String>
+}
+
+struct Input {
+    definition: RoleDefinition,
+    connections: Vec<verified_connections::Model>,
+    /// Every discord user with a connection, as (id, name) pairs in order of appearance
+    users: Vec<(u64, String)>
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match (args.first().map(String::as_str), args.len()) {
+        (Some("users"), 3..=4) => users(&args[1], &args[2], args.get(3)).await,
+        (Some("summary"), 3) => summary(&args[1], &args[2]).await,
+        (Some("report"), 4..=5) => report(&args[1], &args[2], &args[3], args.get(4)).await,
+        (Some("compare"), 4..=5) => compare(&args[1], &args[2], &args[3], args.get(4)).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err.cause);
+        exit(1);
+    }
+}
+
+async fn read_definition(definition_path: &str) -> Result<RoleDefinition, RoleManagerError> {
+    let definition_content = tokio::fs::read_to_string(definition_path).await?;
+
+    json5::from_str(&definition_content)
+        .map_err(|err| RoleManagerError::new(format!("Invalid role definition file {}: {}", definition_path, err)))
+}
+
+async fn read_input(definition_path: &str, connections_path: &str) -> Result<Input, RoleManagerError> {
+    let definition = read_definition(definition_path).await?;
+
+    let connections_content = tokio::fs::read_to_string(connections_path).await?;
+    let rows: Vec<ConnectionRow> = if connections_path.ends_with(".csv") {
+        csv::Reader::from_reader(connections_content.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|err| RoleManagerError::new(format!("Invalid connections file: {}", err)))?
+    } else {
+        json5::from_str(&connections_content)
+            .map_err(|err| RoleManagerError::new(format!("Invalid connections file: {}", err)))?
+    };
+
+    let store = MemoryStore::new();
+    let mut users: Vec<(u64, String)> = Vec::new();
+    for row in &rows {
+        store.add_connection(row.user_id, &row.connection_type, &row.id);
+
+        match users.iter_mut().find(|(id, _)| *id == row.user_id) {
+            Some((_, name)) => {
+                if let Some(user_name) = &row.user_name {
+                    *name = user_name.clone();
+                }
+            }
+            None => users.push((row.user_id, row.user_name.clone().unwrap_or(row.user_id.to_string())))
+        }
+    }
+
+    Ok(Input {
+        definition,
+        connections: store.connections().await?,
+        users
+    })
+}
+
+fn board_states() -> (SrComBoardsState, CmBoardsState) {
+    let srcom_state = SrComBoardsState::new(Duration::minutes(15));
+    let cm_state = CmBoardsState::new(Duration::minutes(15));
+
+    match std::env::var("ROLE_MANAGER_BOARD_CACHE") {
+        Ok(directory) => {
+            let cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(directory));
+            (srcom_state.with_cache(Arc::clone(&cache)), cm_state.with_cache(cache))
+        }
+        Err(_) => (srcom_state, cm_state)
+    }
+}
+
+/// Prints the badges every user (or a single user) has, along with what met each requirement
+async fn users(definition_path: &str, connections_path: &str, user: Option<&String>) -> Result<(), RoleManagerError> {
+    let input = read_input(definition_path, connections_path).await?;
+    let (srcom_state, cm_state) = board_states();
+
+    let user_filter = match user {
+        Some(user) => Some(user.parse::<u64>()
+            .map_err(|err| RoleManagerError::new(format!("Invalid discord id {}: {}", user, err)))?),
+        None => None
+    };
+
+    for (user_id, user_name) in &input.users {
+        if user_filter.is_some_and(|filter| filter != *user_id) {
+            continue;
+        }
+
+        let mut analysis = analyze_user(*user_id, &input.definition, &input.connections, srcom_state.clone(), cm_state.clone(), true).await?;
+        analysis.compute_gaps(&srcom_state, &cm_state).await?;
+
+        println!("== {} ({}) ==", user_name, user_id);
+        for account in &analysis.external_accounts {
+            match account {
+                ExternalAccount::Cm { id, username } => println!("- {} (Steam {})", username, id),
+                ExternalAccount::Srcom { username, link, .. } => println!("- {} (Speedrun.com {})", username, link)
+            }
+        }
+
+        for badge in &analysis.badges {
+            let status = if badge.is_complete() { "complete" } else { "partial" };
+            println!("[{}] {}/{} requirements ({})", badge.definition.name, badge.met_requirements.len(), badge.definition.requirements.len(), status);
+
+            for met_requirement in &badge.met_requirements {
+                println!("{}", met_requirement.describe(srcom_state.clone(), 1).await?);
+            }
+            if !badge.unmet_requirements.is_empty() {
+                println!("  Missing:");
+            }
+            for unmet_requirement in &badge.unmet_requirements {
+                println!("{}", unmet_requirement.describe(srcom_state.clone(), 2).await?);
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Prints how many users meet each badge and requirement, like `/analyze`
+async fn summary(definition_path: &str, connections_path: &str) -> Result<(), RoleManagerError> {
+    let input = read_input(definition_path, connections_path).await?;
+    let (srcom_state, cm_state) = board_states();
+
+    let user_ids = input.users.iter().map(|(id, _)| *id).collect();
+    let report = analyzer::full_analysis(input.definition, input.connections, user_ids, srcom_state.clone(), cm_state, DEFAULT_ANALYSIS_WORKERS).await?;
+
+    println!("Analyzed {} Users ({} CM, {} SRC)", report.total_users, report.steam_users, report.srcom_users);
+    for (badge, requirements) in report.badge_summary(srcom_state).await? {
+        println!("\n{}", badge);
+        println!("{}", requirements);
+    }
+
+    Ok(())
+}
+
+/// Writes the same CSV sheet as `/generate_report` to a file, or stdout if none is given
+async fn report(definition_path: &str, connections_path: &str, badge_name: &str, output: Option<&String>) -> Result<(), RoleManagerError> {
+    let input = read_input(definition_path, connections_path).await?;
+    let (srcom_state, cm_state) = board_states();
+
+    let report = match analyzer::badge_report(&input.definition, badge_name, &input.users, &input.connections, srcom_state, cm_state).await? {
+        Some(report) => report,
+        None => return Err(RoleManagerError::new(format!("This definition file does not contain a badge `{}`", badge_name)))
+    };
+
+    match output {
+        Some(path) => tokio::fs::write(path, &report.csv).await?,
+        None => print!("{}", String::from_utf8_lossy(&report.csv))
+    }
+    eprintln!("{}/{} users meet the requirement for badge {}", report.users_meeting_badge, input.users.len(), badge_name);
+
+    Ok(())
+}
+
+/// Writes a sheet of who would gain, lose or keep each badge when switching definitions, and prints totals per badge
+async fn compare(old_path: &str, new_path: &str, connections_path: &str, output: Option<&String>) -> Result<(), RoleManagerError> {
+    let input = read_input(old_path, connections_path).await?;
+    let new_definition = read_definition(new_path).await?;
+    let (srcom_state, cm_state) = board_states();
+
+    let comparison = compare_definitions(&input.definition, &new_definition, &input.users, &input.connections, srcom_state, cm_state).await?;
+
+    for badge in &comparison.badges {
+        eprintln!("{}: +{} gain, -{} lose, {} keep", badge.badge, badge.gained.len(), badge.lost.len(), badge.kept.len());
+    }
+
+    let csv = comparison.to_csv()?;
+    match output {
+        Some(path) => tokio::fs::write(path, &csv).await?,
+        None => print!("{}", String::from_utf8_lossy(&csv))
+    }
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+use crate::error::RoleManagerError;
+use crate::store::StoreFuture;
+
+/// A response from the boards exactly as it was received, so it can be parsed again after a restart
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedResponse {
+    pub body: String,
+    pub fetched_at: NaiveDateTime
+}
+
+/// Keeps responses from the boards across restarts, keyed by what was requested (e.g. `srcom/games/om1mw4d2`)
+pub trait BoardCache: Send + Sync + Debug {
+    fn load<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<CachedResponse>>;
+
+    fn save<'a>(&'a self, key: &'a str, response: CachedResponse) -> StoreFuture<'a, ()>;
+}
+
+/// Cache which doesn't persist anything, leaving boards only in memory
+#[derive(Debug, Default)]
+pub struct NoBoardCache;
+
+impl BoardCache for NoBoardCache {
+    fn load<'a>(&'a self, _key: &'a str) -> StoreFuture<'a, Option<CachedResponse>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn save<'a>(&'a self, _key: &'a str, _response: CachedResponse) -> StoreFuture<'a, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Cache keeping one JSON file per response within a directory
+#[derive(Debug)]
+pub struct FileBoardCache {
+    directory: PathBuf
+}
+
+impl FileBoardCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into()
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let file_name: String = key.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+
+        self.directory.join(format!("{}.json", file_name))
+    }
+}
+
+impl BoardCache for FileBoardCache {
+    fn load<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<CachedResponse>> {
+        Box::pin(async move {
+            let path = self.path(key);
+            if !tokio::fs::try_exists(&path).await? {
+                return Ok(None);
+            }
+
+            // A damaged entry is downloaded again rather than failing every analysis which needs it
+            match serde_json::from_str(&tokio::fs::read_to_string(&path).await?) {
+                Ok(response) => Ok(Some(response)),
+                Err(err) => {
+                    eprintln!("Ignoring unreadable board cache entry {}: {}", path.display(), err);
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, response: CachedResponse) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.directory).await?;
+
+            // Write to a temporary file first so a crash never leaves half an entry behind
+            let path = self.path(key);
+            let temporary_path = path.with_extension("json.tmp");
+            tokio::fs::write(&temporary_path, serde_json::to_string(&response)?).await?;
+            tokio::fs::rename(&temporary_path, &path).await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Saves a response which was fetched successfully. Failing to persist it only costs a download after a restart, so
+/// the failure is logged rather than failing whatever needed the response.
+pub async fn save_best_effort(cache: &dyn BoardCache, key: &str, response: CachedResponse) {
+    if let Err(err) = cache.save(key, response).await {
+        eprintln!("Failed to save {} to the board cache: {}", key, err);
+    }
+}
+
+/// Something fetched from the boards, along with when it was fetched
+#[derive(Debug)]
+pub struct Cached<T> {
+    pub value: Arc<T>,
+    pub fetched_at: NaiveDateTime
+}
+
+impl<T> Cached<T> {
+    pub fn new(value: Arc<T>, fetched_at: NaiveDateTime) -> Self {
+        Self { value, fetched_at }
+    }
+
+    pub fn is_fresh(&self, cache_persist_time: ChronoDuration) -> bool {
+        self.fetched_at.checked_add_signed(cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
+    }
+}
+
+/// Refreshes of stale entries running in the background, so each entry is only downloaded once at a time
+#[derive(Debug, Default, Clone)]
+pub struct BackgroundRefreshes {
+    in_flight: Arc<Mutex<HashSet<String>>>
+}
+
+impl BackgroundRefreshes {
+    /// Runs `refresh` on its own task, unless `key` is already being refreshed
+    pub fn spawn<F>(&self, key: String, refresh: F) where F: Future<Output = Result<(), RoleManagerError>> + Send + 'static {
+        if !self.in_flight.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let in_flight = Arc::clone(&self.in_flight);
+        tokio::spawn(async move {
+            if let Err(err) = refresh.await {
+                eprintln!("Failed to refresh {} in the background: {}", key, err);
+            }
+
+            in_flight.lock().unwrap().remove(&key);
+        });
+    }
+}
+
+/// Per-key locks, so concurrent fetches of one resource share a single download while other resources download in parallel
+#[derive(Debug, Default, Clone)]
+pub struct SingleFlight {
+    keys: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>
+}
+
+impl SingleFlight {
+    /// Waits until nobody else is fetching `key`. Whoever fetched it before may have cached the resource in the meantime.
+    pub async fn lock(&self, key: &str) -> Flight {
+        let key_lock = Arc::clone(self.keys.lock().unwrap().entry(key.to_string()).or_default());
+
+        Flight {
+            guard: Some(Arc::clone(&key_lock).lock_owned().await),
+            key_lock,
+            key: key.to_string(),
+            keys: Arc::clone(&self.keys)
+        }
+    }
+
+    /// Number of keys which are being fetched or waited on
+    pub fn key_count(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+}
+
+/// A fetch of a single key, forgetting the key's lock once it ends and nobody is waiting for it
+#[derive(Debug)]
+pub struct Flight {
+    guard: Option<OwnedMutexGuard<()>>,
+    key_lock: Arc<tokio::sync::Mutex<()>>,
+    key: String,
+    keys: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+
+        // New waiters clone the lock while holding the map, so with only the map and this flight left nobody else can be waiting
+        let mut keys = self.keys.lock().unwrap();
+        if Arc::strong_count(&self.key_lock) == 2 && keys.get(&self.key).is_some_and(|key_lock| Arc::ptr_eq(key_lock, &self.key_lock)) {
+            keys.remove(&self.key);
+        }
+    }
+}
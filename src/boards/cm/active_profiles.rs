@@ -1,14 +1,6 @@
-use std::sync::Arc;
-use chrono::NaiveDateTime;
 use serde::Deserialize;
 use crate::error::RoleManagerError;
 
-#[derive(Debug)]
-pub struct CachedActiveProfiles {
-    pub active_profiles: Arc<Vec<String>>,
-    pub fetched_at: NaiveDateTime
-}
-
 #[derive(Deserialize, Debug)]
 struct ActiveProfile {
     profile_number: String
@@ -19,14 +11,19 @@ struct ActiveProfilesResponse {
     profiles: Vec<ActiveProfile>
 }
 
-pub async fn fetch_active_profiles(months: u64) -> Result<Vec<String>, RoleManagerError> {
+pub async fn fetch_active_profiles(base_url: &str, months: u64) -> Result<String, RoleManagerError> {
     let client = reqwest::Client::new();
-    Ok(client.post("https://board.portal2.sr/api-v2/active-profiles")
+    Ok(client.post(format!("{}/api-v2/active-profiles", base_url))
         .form(&[("months", months)])
         .send()
         .await.map_err(|err| format!("Failed to request active profiles on board.portal2.sr: {}", err))?
-        .json::<ActiveProfilesResponse>()
-        .await.map_err(|err| format!("Failed to convert response from active profiles on board.portal2.sr: {}", err))?
+        .text()
+        .await.map_err(|err| format!("Failed to read response from active profiles on board.portal2.sr: {}", err))?)
+}
+
+pub fn parse_active_profiles(body: &str) -> Result<Vec<String>, RoleManagerError> {
+    Ok(serde_json::from_str::<ActiveProfilesResponse>(body)
+        .map_err(|err| format!("Failed to convert response from active profiles on board.portal2.sr: {}", err))?
         .profiles
         .into_iter().map(|profile| profile.profile_number).collect())
 }
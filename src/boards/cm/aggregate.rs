@@ -1,6 +1,4 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use chrono::NaiveDateTime;
 use serde::Deserialize;
 use crate::boards::cm::profile::Profile;
 use crate::error::RoleManagerError;
@@ -28,15 +26,14 @@ pub struct AggregatedResponse {
     pub points: HashMap<String, AggregatedPlace>
 }
 
-#[derive(Debug)]
-pub struct CachedAggregate {
-    pub aggregate: Arc<AggregatedResponse>,
-    pub fetched_at: NaiveDateTime
+pub async fn fetch_aggregate(base_url: &str, page: &str) -> Result<String, RoleManagerError> {
+    Ok(reqwest::get(format!("{}/{}/json", base_url, page))
+        .await.map_err(|err| format!("Failed to request {} page on board.portal2.sr: {}", page, err) )?
+        .text()
+        .await.map_err(|err| format!("Failed to read response from {} page on board.portal2.sr: {}", page, err) )?)
 }
 
-pub async fn fetch_aggregate(page: &str) -> Result<AggregatedResponse, RoleManagerError> {
-    Ok(reqwest::get(format!("https://board.portal2.sr/{}/json", page))
-        .await.map_err(|err| format!("Failed to request {} page on board.portal2.sr: {}", page, err) )?
-        .json::<AggregatedResponse>()
-        .await.map_err(|err| format!("Failed to convert response from {} page on board.portal2.sr: {}", page, err) )?)
+pub fn parse_aggregate(page: &str, body: &str) -> Result<AggregatedResponse, RoleManagerError> {
+    Ok(serde_json::from_str(body)
+        .map_err(|err| format!("Failed to convert response from {} page on board.portal2.sr: {}", page, err) )?)
 }
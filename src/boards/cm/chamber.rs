@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use serde::{de, Deserialize, Deserializer};
+use crate::boards::cm::profile::Profile;
+use crate::error::RoleManagerError;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChamberScoreData {
+    #[serde(deserialize_with = "number_or_string")]
+    pub score: u32,
+    #[serde(rename = "playerRank", deserialize_with = "number_or_string")]
+    pub player_rank: u32,
+    #[serde(rename = "scoreRank", deserialize_with = "number_or_string")]
+    pub score_rank: u32,
+    pub date: Option<String>
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChamberPlace {
+    #[serde(rename = "userData")]
+    pub user_data: Profile,
+    #[serde(rename = "scoreData")]
+    pub score_data: ChamberScoreData
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ChamberResponse {
+    pub points: HashMap<String, ChamberPlace>
+}
+
+/// The boards serve most numeric columns of a chamber as strings, so accept either representation
+fn number_or_string<'de, D>(deserializer: D) -> Result<u32, D::Error> where D: Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u32),
+        String(String)
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.parse().map_err(de::Error::custom)
+    }
+}
+
+pub async fn fetch_chamber(base_url: &str, id: u64) -> Result<String, RoleManagerError> {
+    Ok(reqwest::get(format!("{}/chamber/{}/json", base_url, id))
+        .await.map_err(|err| format!("Failed to request chamber {} on board.portal2.sr: {}", id, err))?
+        .text()
+        .await.map_err(|err| format!("Failed to read response from chamber {} on board.portal2.sr: {}", id, err))?)
+}
+
+pub fn parse_chamber(id: u64, body: &str) -> Result<ChamberResponse, RoleManagerError> {
+    Ok(serde_json::from_str(body)
+        .map_err(|err| format!("Failed to convert response from chamber {} on board.portal2.sr: {}", id, err))?)
+}
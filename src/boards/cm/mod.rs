@@ -1,118 +1,260 @@
 mod aggregate;
 mod active_profiles;
+pub mod chamber;
 mod profile;
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use chrono::{Duration as ChronoDuration, Utc};
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
 
 use tokio::sync::Mutex;
 use crate::analyzer::role_definition::CmLeaderboard;
-use crate::boards::cm::active_profiles::CachedActiveProfiles;
-use crate::boards::cm::aggregate::{AggregatedResponse, CachedAggregate};
-use crate::boards::cm::profile::{CachedProfile, Profile};
+use crate::boards::cache::{save_best_effort, BackgroundRefreshes, BoardCache, Cached, CachedResponse, NoBoardCache};
+use crate::boards::cm::aggregate::AggregatedResponse;
+use crate::boards::cm::chamber::ChamberResponse;
+use crate::boards::cm::profile::Profile;
 use crate::error::RoleManagerError;
 
+/// The CM boards used unless another base url is provided
+pub const CM_BOARDS_URL: &str = "https://board.portal2.sr";
+
 #[derive(Debug, Clone)]
 pub struct CmBoardsState {
+    base_url: String,
+    cache: Arc<dyn BoardCache>,
+    refreshes: BackgroundRefreshes,
     cache_persist_time: ChronoDuration,
 
-    cached_aggregates: Arc<Mutex<HashMap<CmLeaderboard, CachedAggregate>>>,
-    cached_active_profiles: Arc<Mutex<HashMap<u64, CachedActiveProfiles>>>,
-    cached_profiles: Arc<Mutex<HashMap<i64, CachedProfile>>>
+    cached_aggregates: Arc<Mutex<HashMap<CmLeaderboard, Cached<AggregatedResponse>>>>,
+    cached_active_profiles: Arc<Mutex<HashMap<u64, Cached<Vec<String>>>>>,
+    cached_chambers: Arc<Mutex<HashMap<u64, Cached<ChamberResponse>>>>,
+    cached_profiles: Arc<Mutex<HashMap<i64, Cached<Profile>>>>
 }
 
 impl CmBoardsState {
     pub fn new(cache_persist_time: ChronoDuration) -> Self {
+        Self::with_base_url(cache_persist_time, CM_BOARDS_URL.to_string())
+    }
+
+    /// Creates a state which sends its requests to another instance of the boards, such as a local mock
+    pub fn with_base_url(cache_persist_time: ChronoDuration, base_url: String) -> Self {
         CmBoardsState {
+            base_url,
+            cache: Arc::new(NoBoardCache),
+            refreshes: BackgroundRefreshes::default(),
             cache_persist_time,
 
             cached_aggregates: Arc::new(Mutex::new(HashMap::new())),
             cached_active_profiles: Arc::new(Mutex::new(HashMap::new())),
+            cached_chambers: Arc::new(Mutex::new(HashMap::new())),
             cached_profiles: Arc::new(Mutex::new(HashMap::new()))
         }
     }
 
+    /// Keeps downloaded responses in `cache`, so they survive restarts.
+    /// Profiles embedded in aggregates and chambers are restored along with them.
+    pub fn with_cache(mut self, cache: Arc<dyn BoardCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
     pub async fn fetch_aggregate(&self, leaderboard: &CmLeaderboard) -> Result<Arc<AggregatedResponse>, RoleManagerError> {
+        let page = aggregate_page(leaderboard);
+        let key = format!("cm/{}", page);
         let mut cache = self.cached_aggregates.lock().await;
-        let mut cached_profiles = self.cached_profiles.lock().await;
 
-        match cache.get(leaderboard).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_aggregate) => {
-                Ok(Arc::clone(&cached_aggregate.aggregate))
-            }
-            None => {
-                let page = match leaderboard {
-                    CmLeaderboard::Overall => "aggregated/overall",
-                    CmLeaderboard::SinglePlayer => "aggregated/sp",
-                    CmLeaderboard::Coop => "aggregated/coop"
-                };
-
-                let aggregate = Arc::new(aggregate::fetch_aggregate(page).await?);
-
-                for pair in &aggregate.points {
-                    cached_profiles.insert(pair.0.parse()
-                                               .map_err(|err| format!("CM Boards provided invalid steam id: {}", err))?,
-                                           CachedProfile {
-                                               profile: Arc::new(pair.1.user_data.clone()),
-                                               fetched_at: Utc::now().naive_utc()
-                                           });
-                }
-
-                cache.insert(*leaderboard, CachedAggregate {
-                    aggregate: Arc::clone(&aggregate),
-                    fetched_at: Utc::now().naive_utc()
+        if !cache.contains_key(leaderboard) && let Some(persisted) = self.cache.load(&key).await? {
+            let aggregate = Arc::new(aggregate::parse_aggregate(page, &persisted.body)?);
+            self.cache_profiles(aggregate.points.iter().map(|(id, place)| (id, &place.user_data)), persisted.fetched_at).await?;
+            cache.insert(*leaderboard, Cached::new(aggregate, persisted.fetched_at));
+        }
+
+        if let Some(cached_aggregate) = cache.get(leaderboard) {
+            if !cached_aggregate.is_fresh(self.cache_persist_time) {
+                // Serve the stale aggregate while a newer one is downloaded
+                let state = self.clone();
+                let leaderboard = *leaderboard;
+                self.refreshes.spawn(key, async move {
+                    let body = aggregate::fetch_aggregate(&state.base_url, page).await?;
+                    state.remember_aggregate(&mut *state.cached_aggregates.lock().await, leaderboard, body).await?;
+                    Ok(())
                 });
+            }
+
+            return Ok(Arc::clone(&cached_aggregate.value));
+        }
+
+        let body = aggregate::fetch_aggregate(&self.base_url, page).await?;
+        self.remember_aggregate(&mut cache, *leaderboard, body).await
+    }
 
-                Ok(aggregate)
+    async fn remember_aggregate(
+        &self,
+        cache: &mut HashMap<CmLeaderboard, Cached<AggregatedResponse>>,
+        leaderboard: CmLeaderboard,
+        body: String
+    ) -> Result<Arc<AggregatedResponse>, RoleManagerError> {
+        let page = aggregate_page(&leaderboard);
+        let fetched_at = Utc::now().naive_utc();
+        let aggregate = Arc::new(aggregate::parse_aggregate(page, &body)?);
+
+        self.cache_profiles(aggregate.points.iter().map(|(id, place)| (id, &place.user_data)), fetched_at).await?;
+        save_best_effort(self.cache.as_ref(), &format!("cm/{}", page), CachedResponse { body, fetched_at }).await;
+        cache.insert(leaderboard, Cached::new(Arc::clone(&aggregate), fetched_at));
+
+        Ok(aggregate)
+    }
+
+    pub async fn fetch_chamber(&self, chamber: u64) -> Result<Arc<ChamberResponse>, RoleManagerError> {
+        let key = format!("cm/chamber/{}", chamber);
+        let mut cache = self.cached_chambers.lock().await;
+
+        if !cache.contains_key(&chamber) && let Some(persisted) = self.cache.load(&key).await? {
+            let response = Arc::new(chamber::parse_chamber(chamber, &persisted.body)?);
+            self.cache_profiles(response.points.iter().map(|(id, place)| (id, &place.user_data)), persisted.fetched_at).await?;
+            cache.insert(chamber, Cached::new(response, persisted.fetched_at));
+        }
+
+        if let Some(cached_chamber) = cache.get(&chamber) {
+            if !cached_chamber.is_fresh(self.cache_persist_time) {
+                // Serve the stale chamber while a newer one is downloaded
+                let state = self.clone();
+                self.refreshes.spawn(key, async move {
+                    let body = chamber::fetch_chamber(&state.base_url, chamber).await?;
+                    state.remember_chamber(&mut *state.cached_chambers.lock().await, chamber, body).await?;
+                    Ok(())
+                });
             }
+
+            return Ok(Arc::clone(&cached_chamber.value));
+        }
+
+        let body = chamber::fetch_chamber(&self.base_url, chamber).await?;
+        self.remember_chamber(&mut cache, chamber, body).await
+    }
+
+    async fn remember_chamber(
+        &self,
+        cache: &mut HashMap<u64, Cached<ChamberResponse>>,
+        chamber: u64,
+        body: String
+    ) -> Result<Arc<ChamberResponse>, RoleManagerError> {
+        let fetched_at = Utc::now().naive_utc();
+        let response = Arc::new(chamber::parse_chamber(chamber, &body)?);
+
+        self.cache_profiles(response.points.iter().map(|(id, place)| (id, &place.user_data)), fetched_at).await?;
+        save_best_effort(self.cache.as_ref(), &format!("cm/chamber/{}", chamber), CachedResponse { body, fetched_at }).await;
+        cache.insert(chamber, Cached::new(Arc::clone(&response), fetched_at));
+
+        Ok(response)
+    }
+
+    /// Caches the profiles embedded in an aggregate or chamber, keyed by their steam id
+    async fn cache_profiles<'a>(
+        &self,
+        profiles: impl Iterator<Item = (&'a String, &'a Profile)>,
+        fetched_at: NaiveDateTime
+    ) -> Result<(), RoleManagerError> {
+        let mut cached_profiles = self.cached_profiles.lock().await;
+
+        for (steam_id, profile) in profiles {
+            cached_profiles.insert(steam_id.parse()
+                                       .map_err(|err| format!("CM Boards provided invalid steam id: {}", err))?,
+                                   Cached::new(Arc::new(profile.clone()), fetched_at));
         }
+
+        Ok(())
     }
 
     pub async fn fetch_active_profiles(&self, months: u64) -> Result<Arc<Vec<String>>, RoleManagerError> {
+        let key = format!("cm/active-profiles/{}", months);
         let mut cache = self.cached_active_profiles.lock().await;
 
-        match cache.get(&months).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_profiles) => {
-                Ok(Arc::clone(&cached_profiles.active_profiles))
-            }
-            None => {
-                let profiles = Arc::new(active_profiles::fetch_active_profiles(months).await?);
+        if !cache.contains_key(&months) && let Some(persisted) = self.cache.load(&key).await? {
+            let profiles = Arc::new(active_profiles::parse_active_profiles(&persisted.body)?);
+            cache.insert(months, Cached::new(profiles, persisted.fetched_at));
+        }
 
-                cache.insert(months, CachedActiveProfiles {
-                    active_profiles: Arc::clone(&profiles),
-                    fetched_at: Utc::now().naive_utc()
+        if let Some(cached_profiles) = cache.get(&months) {
+            if !cached_profiles.is_fresh(self.cache_persist_time) {
+                // Serve the stale profiles while newer ones are downloaded
+                let state = self.clone();
+                self.refreshes.spawn(key, async move {
+                    let body = active_profiles::fetch_active_profiles(&state.base_url, months).await?;
+                    state.remember_active_profiles(&mut *state.cached_active_profiles.lock().await, months, body).await?;
+                    Ok(())
                 });
-
-                Ok(profiles)
             }
+
+            return Ok(Arc::clone(&cached_profiles.value));
         }
+
+        let body = active_profiles::fetch_active_profiles(&self.base_url, months).await?;
+        self.remember_active_profiles(&mut cache, months, body).await
+    }
+
+    async fn remember_active_profiles(
+        &self,
+        cache: &mut HashMap<u64, Cached<Vec<String>>>,
+        months: u64,
+        body: String
+    ) -> Result<Arc<Vec<String>>, RoleManagerError> {
+        let fetched_at = Utc::now().naive_utc();
+        let profiles = Arc::new(active_profiles::parse_active_profiles(&body)?);
+
+        save_best_effort(self.cache.as_ref(), &format!("cm/active-profiles/{}", months), CachedResponse { body, fetched_at }).await;
+        cache.insert(months, Cached::new(Arc::clone(&profiles), fetched_at));
+
+        Ok(profiles)
     }
 
     pub async fn fetch_profile(&self, id: i64) -> Result<Arc<Profile>, RoleManagerError> {
+        let key = format!("cm/profile/{}", id);
         let mut cache = self.cached_profiles.lock().await;
 
-        match cache.get(&id).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_profile) => {
-                Ok(Arc::clone(&cached_profile.profile))
-            }
-            None => {
-                let profile = Arc::new(profile::fetch_profile(id).await?);
+        if !cache.contains_key(&id) && let Some(persisted) = self.cache.load(&key).await? {
+            let profile = Arc::new(profile::parse_profile(id, &persisted.body)?);
+            cache.insert(id, Cached::new(profile, persisted.fetched_at));
+        }
 
-                cache.insert(id, CachedProfile {
-                    profile: Arc::clone(&profile),
-                    fetched_at: Utc::now().naive_utc()
+        if let Some(cached_profile) = cache.get(&id) {
+            if !cached_profile.is_fresh(self.cache_persist_time) {
+                // Serve the stale profile while a newer one is downloaded
+                let state = self.clone();
+                self.refreshes.spawn(key, async move {
+                    let body = profile::fetch_profile(&state.base_url, id).await?;
+                    state.remember_profile(&mut *state.cached_profiles.lock().await, id, body).await?;
+                    Ok(())
                 });
-
-                Ok(profile)
             }
+
+            return Ok(Arc::clone(&cached_profile.value));
         }
+
+        let body = profile::fetch_profile(&self.base_url, id).await?;
+        self.remember_profile(&mut cache, id, body).await
+    }
+
+    async fn remember_profile(
+        &self,
+        cache: &mut HashMap<i64, Cached<Profile>>,
+        id: i64,
+        body: String
+    ) -> Result<Arc<Profile>, RoleManagerError> {
+        let fetched_at = Utc::now().naive_utc();
+        let profile = Arc::new(profile::parse_profile(id, &body)?);
+
+        save_best_effort(self.cache.as_ref(), &format!("cm/profile/{}", id), CachedResponse { body, fetched_at }).await;
+        cache.insert(id, Cached::new(Arc::clone(&profile), fetched_at));
+
+        Ok(profile)
     }
 }
 
+fn aggregate_page(leaderboard: &CmLeaderboard) -> &'static str {
+    match leaderboard {
+        CmLeaderboard::Overall => "aggregated/overall",
+        CmLeaderboard::SinglePlayer => "aggregated/sp",
+        CmLeaderboard::Coop => "aggregated/coop"
+    }
+}
@@ -1,5 +1,3 @@
-use std::sync::Arc;
-use chrono::NaiveDateTime;
 use serde::Deserialize;
 use crate::error::RoleManagerError;
 
@@ -18,16 +16,15 @@ pub struct ProfileResponse {
     pub user_data: Profile
 }
 
-#[derive(Debug)]
-pub struct CachedProfile {
-    pub profile: Arc<Profile>,
-    pub fetched_at: NaiveDateTime
+pub async fn fetch_profile(base_url: &str, id: i64) -> Result<String, RoleManagerError> {
+    Ok(reqwest::get(format!("{}/profile/{}/json", base_url, id))
+        .await.map_err(|err| format!("Failed to request profile for steam id {} on board.portal2.sr: {}", id, err))?
+        .text()
+        .await.map_err(|err| format!("Failed to read response from profile for steam id {} on board.portal2.sr: {}", id, err))?)
 }
 
-pub async fn fetch_profile(id: i64) -> Result<Profile, RoleManagerError> {
-    Ok(reqwest::get(format!("https://board.portal2.sr/profile/{}/json", id))
-        .await.map_err(|err| format!("Failed to request profile for steam id {} on board.portal2.sr: {}", id, err))?
-        .json::<ProfileResponse>()
-        .await.map_err(|err| format!("Failed to convert response from profile for steam id {} on board.portal2.sr: {}", id, err))?
+pub fn parse_profile(id: i64, body: &str) -> Result<Profile, RoleManagerError> {
+    Ok(serde_json::from_str::<ProfileResponse>(body)
+        .map_err(|err| format!("Failed to convert response from profile for steam id {} on board.portal2.sr: {}", id, err))?
         .user_data)
 }
@@ -1,2 +1,4 @@
+pub mod cache;
+pub mod retry;
 pub mod cm;
 pub mod srcom;
@@ -0,0 +1,191 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use reqwest::{Client, Request, Response, StatusCode};
+use reqwest::header::RETRY_AFTER;
+use tower::limit::RateLimit;
+use tower::limit::rate::Rate;
+use tower::retry::Policy;
+use tower::{Service, ServiceExt};
+use crate::error::RoleManagerError;
+
+/// How often and how patiently failed requests to the boards are retried
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Retries after the first attempt, before the failure is reported
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for every further retry
+    pub initial_backoff: Duration,
+    /// Longest wait between two attempts, also capping `Retry-After`
+    pub max_backoff: Duration,
+    /// Consecutive failed requests after which requests fail immediately
+    pub failure_threshold: u32,
+    /// How long requests fail immediately once the failure threshold is reached
+    pub cooldown: Duration
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(120)
+        }
+    }
+}
+
+/// Whether a response means the request may succeed when sent again: throttling (speedrun.com answers 420 rather
+/// than 429) or a server error
+pub fn is_retryable(status: StatusCode) -> bool {
+    status.as_u16() == 420 || status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Retries requests failing with a network error or a retryable status, waiting longer after every attempt
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    config: RetryConfig,
+    attempt: u32
+}
+
+impl RetryPolicy {
+    pub fn new(config: RetryConfig) -> Self {
+        Self { config, attempt: 0 }
+    }
+
+    fn backoff(&self) -> Duration {
+        self.config.initial_backoff
+            .checked_mul(2u32.saturating_pow(self.attempt))
+            .map_or(self.config.max_backoff, |backoff| backoff.min(self.config.max_backoff))
+    }
+}
+
+impl Policy<Request, Response, reqwest::Error> for RetryPolicy {
+    type Future = Pin<Box<dyn Future<Output = Self> + Send>>;
+
+    fn retry(&self, _req: &Request, result: Result<&Response, &reqwest::Error>) -> Option<Self::Future> {
+        if self.attempt >= self.config.max_retries {
+            return None;
+        }
+
+        let delay = match result {
+            Ok(response) if is_retryable(response.status()) => retry_after(response)
+                .map_or_else(|| self.backoff(), |delay| delay.min(self.config.max_backoff)),
+            Ok(_) => return None,
+            Err(err) if err.is_builder() => return None,
+            Err(_) => self.backoff()
+        };
+
+        let next = Self {
+            config: self.config.clone(),
+            attempt: self.attempt + 1
+        };
+        Some(Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            next
+        }))
+    }
+
+    fn clone_request(&self, req: &Request) -> Option<Request> {
+        req.try_clone()
+    }
+}
+
+/// A rate limited client shared by all of its clones, so every attempt made by a `RetryPolicy` wrapping it waits for
+/// a permit of its own. The limiter is only held while waiting, so responses download in parallel.
+#[derive(Debug, Clone)]
+pub struct SharedRateLimit {
+    limiter: Arc<tokio::sync::Mutex<RateLimit<Client>>>
+}
+
+impl SharedRateLimit {
+    pub fn new(client: Client, num: u64, per: Duration) -> Self {
+        Self {
+            limiter: Arc::new(tokio::sync::Mutex::new(RateLimit::new(client, Rate::new(num, per))))
+        }
+    }
+}
+
+impl Service<Request> for SharedRateLimit {
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Readiness is awaited in `call`, while holding the shared limiter
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let limiter = Arc::clone(&self.limiter);
+        Box::pin(async move {
+            let response = {
+                let mut limiter = limiter.lock().await;
+                limiter.ready().await?.call(req)
+            };
+            response.await
+        })
+    }
+}
+
+/// The wait requested by the `Retry-After` header, when given in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    response.headers().get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim().parse::<u64>().ok()
+        .map(Duration::from_secs)
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>
+}
+
+/// Stops sending requests for a while once the boards keep failing, so a pass doesn't wait out every retry of every
+/// request while they are down
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Arc<Mutex<BreakerState>>
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &RetryConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: config.cooldown,
+            state: Arc::new(Mutex::new(BreakerState::default()))
+        }
+    }
+
+    /// Fails while the breaker is open. After the cooldown, requests are let through again to probe the boards.
+    pub fn check(&self, boards: &str) -> Result<(), RoleManagerError> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if open_until > Instant::now() => Err(RoleManagerError::new(format!(
+                "Not contacting {} for another {} seconds after {} failed requests in a row",
+                boards, (open_until - Instant::now()).as_secs() + 1, state.consecutive_failures
+            ))),
+            _ => Ok(())
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
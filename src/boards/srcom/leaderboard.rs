@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 use serde::Deserialize;
 use crate::analyzer::role_definition::PartnerRestriction;
 use crate::boards::srcom::category::{CategoryOrId};
@@ -28,10 +29,91 @@ pub struct Leaderboard {
     pub runs: Vec<Arc<LeaderboardPlace>>,
     pub links: Option<Vec<Link>>,
     pub players: Option<MultipleItemRequest<UserOrGuest>>,
-    pub variables: Option<MultipleItemRequest<Variable>>,
+    pub variables: Option<MultipleItemRequest<Variable>>
+}
+
+/// Narrows a leaderboard down beyond its variables, as the leaderboard endpoint allows
+#[derive(Deserialize, Debug, Clone, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct BoardFilters {
+    pub platform: Option<PlatformId>,
+    pub region: Option<RegionId>,
+    /// Whether runs on emulators are included, or only runs on emulators with `true`
+    pub emulators: Option<bool>,
+    pub video_only: Option<bool>,
+    /// Timing method the board is ranked by, instead of the game's default
+    pub timing: Option<TimingMethod>,
+    /// Shows the board as it was on this date (YYYY-MM-DD)
+    pub date: Option<String>
+}
+
+impl BoardFilters {
+    /// Query parameters for the leaderboard endpoint
+    pub fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![];
+        if let Some(platform) = &self.platform {
+            pairs.push(("platform", platform.0.clone()));
+        }
+        if let Some(region) = &self.region {
+            pairs.push(("region", region.0.clone()));
+        }
+        if let Some(emulators) = self.emulators {
+            pairs.push(("emulators", emulators.to_string()));
+        }
+        if let Some(video_only) = self.video_only {
+            pairs.push(("video-only", video_only.to_string()));
+        }
+        if let Some(timing) = self.timing {
+            pairs.push(("timing", timing.api_name().to_string()));
+        }
+        if let Some(date) = &self.date {
+            pairs.push(("date", date.clone()));
+        }
+
+        pairs
+    }
+
+    /// Whether a run would show up on a board narrowed down by these filters. The timing method only changes how
+    /// the board is ranked, so it never excludes a run.
+    pub fn matches(&self, run: &Run) -> bool {
+        let has_video = run.videos.as_ref()
+            .and_then(|videos| videos.links.as_ref())
+            .is_some_and(|links| !links.is_empty());
+
+        self.platform.as_ref().is_none_or(|platform| run.system.platform.as_ref() == Some(platform))
+            && self.region.as_ref().is_none_or(|region| run.system.region.as_ref() == Some(region))
+            && self.emulators.is_none_or(|emulators| run.system.emulated == emulators)
+            && (self.video_only != Some(true) || has_video)
+            // Both dates are YYYY-MM-DD, so they compare in order as strings
+            && self.date.as_ref().is_none_or(|date| run.date.as_ref().is_some_and(|run_date| run_date <= date))
+    }
+}
+
+impl Display for BoardFilters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some(platform) = &self.platform {
+            parts.push(format!("platform {}", platform.0));
+        }
+        if let Some(region) = &self.region {
+            parts.push(format!("region {}", region.0));
+        }
+        match self.emulators {
+            Some(true) => parts.push("emulators only".to_string()),
+            Some(false) => parts.push("no emulators".to_string()),
+            None => {}
+        }
+        if self.video_only == Some(true) {
+            parts.push("video only".to_string());
+        }
+        if let Some(timing) = self.timing {
+            parts.push(format!("by {}", timing));
+        }
+        if let Some(date) = &self.date {
+            parts.push(format!("as of {}", date));
+        }
 
-    #[serde(default)]
-    pub user_highest_run_cache: Mutex<HashMap<(UserId, Option<PartnerRestriction>), Option<Arc<LeaderboardPlace>>>>
+        write!(f, "{}", parts.join(", "))
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -40,61 +122,68 @@ pub struct LeaderboardPlace {
     pub run: Run
 }
 
-impl Leaderboard {
-    pub fn get_highest_run(&self, user_id: UserId, partner_restriction: Option<PartnerRestriction>) -> Option<Arc<LeaderboardPlace>> {
-        if let Some(highest_run) = self.user_highest_run_cache
-            .lock().unwrap()
-            .get(&(user_id, partner_restriction)) {
+/// A leaderboard along with every user's best places on it, indexed once when the board is loaded
+#[derive(Debug)]
+pub struct IndexedLeaderboard {
+    pub board: Leaderboard,
+    /// Each user's best verified place
+    best_places: HashMap<UserId, Arc<LeaderboardPlace>>,
+    /// Each user's best verified place where every partner ranks at or below it on their own
+    rank_gte_places: HashMap<UserId, Arc<LeaderboardPlace>>
+}
 
-            return highest_run.as_ref().map(|p| Arc::clone(p));
+impl IndexedLeaderboard {
+    pub fn new(board: Leaderboard) -> Self {
+        let mut best_places = HashMap::new();
+        for run in ranked_runs(&board) {
+            for id in run_users(run) {
+                insert_if_better(&mut best_places, id, run);
+            }
         }
 
-        let mut best_place: Option<&Arc<LeaderboardPlace>> = None;
-
-        for run in &self.runs {
-            let mut player_match = false;
-            let mut other_players_meet_restriction = true;
-            for p in &(run.run.players) {
-                if let RunPlayer::User { id, .. } = p {
-                    if *id == user_id {
-                        player_match = true;
-                    } else {
-                        // Check this user against the partner restriction
-                        match partner_restriction {
-                            Some(PartnerRestriction::RankGte) => {
-                                match self.get_highest_run(*id, None) {
-                                    Some(partner_place) => {
-                                        if partner_place.place < run.place {
-                                            other_players_meet_restriction = false;
-                                        }
-                                    }
-                                    None => {}
-                                }
-                            }
-                            None => {}
-                        }
-                    }
-                }
-            }
+        // Partners are judged by their own best place, so this needs every best place first
+        let mut rank_gte_places = HashMap::new();
+        for run in ranked_runs(&board) {
+            for id in run_users(run) {
+                let partners_meet_restriction = run_users(run)
+                    .filter(|partner| *partner != id)
+                    .all(|partner| best_places.get(&partner).is_none_or(|partner_place| partner_place.place >= run.place));
 
-            if player_match
-                && matches!(run.run.status, RunStatus::Verified {..})
-                && other_players_meet_restriction
-                && run.place != 0 {
-                if match best_place {
-                    Some(best) => run.place < best.place,
-                    None => true
-                } {
-                    best_place = Some(&run)
+                if partners_meet_restriction {
+                    insert_if_better(&mut rank_gte_places, id, run);
                 }
             }
         }
 
-        self.user_highest_run_cache
-            .lock().unwrap()
-            .insert((user_id, partner_restriction), best_place.map(|p| Arc::clone(p)));
+        Self { board, best_places, rank_gte_places }
+    }
+
+    pub fn get_highest_run(&self, user_id: UserId, partner_restriction: Option<PartnerRestriction>) -> Option<Arc<LeaderboardPlace>> {
+        let places = match partner_restriction {
+            None => &self.best_places,
+            Some(PartnerRestriction::RankGte) => &self.rank_gte_places
+        };
+
+        places.get(&user_id).cloned()
+    }
+}
+
+/// Verified runs which hold a place on the board
+fn ranked_runs(board: &Leaderboard) -> impl Iterator<Item = &Arc<LeaderboardPlace>> {
+    board.runs.iter()
+        .filter(|run| matches!(run.run.status, RunStatus::Verified {..}) && run.place != 0)
+}
+
+fn run_users(run: &LeaderboardPlace) -> impl Iterator<Item = UserId> + '_ {
+    run.run.players.iter().filter_map(|player| match player {
+        RunPlayer::User { id, .. } => Some(*id),
+        _ => None
+    })
+}
 
-        best_place.map(|p| Arc::clone(p))
+fn insert_if_better(places: &mut HashMap<UserId, Arc<LeaderboardPlace>>, user_id: UserId, run: &Arc<LeaderboardPlace>) {
+    if places.get(&user_id).is_none_or(|best| run.place < best.place) {
+        places.insert(user_id, Arc::clone(run));
     }
 }
 
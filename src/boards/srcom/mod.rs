@@ -9,21 +9,27 @@ pub mod run;
 pub mod user;
 
 use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
 use std::sync::Arc;
 use std::time::Duration;
 use chrono::{NaiveDateTime, Utc};
 use chrono::Duration as ChronoDuration;
+use futures::{Stream, TryStreamExt};
 use reqwest::{Client, Method, Request, Response, Url};
 use serde::{Deserialize};
+use serde::de::DeserializeOwned;
 use tokio::sync::Mutex;
-use tower::limit::RateLimit;
-use tower::Service;
+use tower::retry::Retry;
 use tower::ServiceExt;
 use crate::analyzer::role_definition::PartnerRestriction;
+use crate::boards::cache::{save_best_effort, BackgroundRefreshes, BoardCache, Cached, CachedResponse, NoBoardCache, SingleFlight};
+use crate::boards::retry::{is_retryable, CircuitBreaker, RetryConfig, RetryPolicy, SharedRateLimit};
 use crate::boards::srcom::category::{Category, CategoryId, CategoryOrId};
 use crate::boards::srcom::game::{Game, GameId, GameOrId};
-use crate::boards::srcom::leaderboard::{Leaderboard, LeaderboardPlace, UserOrGuest};
-use crate::boards::srcom::level::LevelId;
+use crate::boards::srcom::leaderboard::{BoardFilters, IndexedLeaderboard, Leaderboard, LeaderboardPlace, UserOrGuest};
+use crate::boards::srcom::level::{Level, LevelId};
+use crate::boards::srcom::run::{Run, RunStatus, RunsQuery};
 use crate::boards::srcom::user::{User, UserId};
 use crate::boards::srcom::variable::{Variable, VariableId, VariableValueId};
 use crate::error::RoleManagerError;
@@ -41,7 +47,7 @@ pub struct Link {
     pub uri: String
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub enum TimingMethod {
     #[serde(rename = "realtime")]
     RealTime,
@@ -51,66 +57,127 @@ pub enum TimingMethod {
     InGame
 }
 
+impl TimingMethod {
+    /// The name speedrun.com uses for the timing method in requests
+    pub fn api_name(&self) -> &'static str {
+        match self {
+            Self::RealTime => "realtime",
+            Self::RealTimeNoLoads => "realtime_noloads",
+            Self::InGame => "ingame"
+        }
+    }
+}
+
+impl Display for TimingMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RealTime => write!(f, "RTA"),
+            Self::RealTimeNoLoads => write!(f, "LRT"),
+            Self::InGame => write!(f, "IGT")
+        }
+    }
+}
+
+/// The speedrun.com API used unless another base url is provided
+pub const SRCOM_API_URL: &str = "https://www.speedrun.com/api/v1";
+
+/// A user's most recent run on a board, if they have any
+type LatestRun = Option<Arc<Run>>;
+
 #[derive(Clone, Debug)]
 pub struct SrComBoardsState {
-    rate_limited_client: Arc<Mutex<RateLimit<Client>>>,
+    client: Retry<RetryPolicy, SharedRateLimit>,
+    circuit_breaker: CircuitBreaker,
+    base_url: String,
 
+    cache: Arc<dyn BoardCache>,
+    refreshes: BackgroundRefreshes,
+    single_flight: SingleFlight,
     cache_persist_time: ChronoDuration,
-    cached_boards: Arc<Mutex<HashMap<BoardDefinition, CachedBoard>>>,
-    cached_games: Arc<Mutex<HashMap<GameId, CachedGame>>>,
-    cached_categories: Arc<Mutex<HashMap<CategoryId, CachedCategory>>>,
-    cached_users: Arc<Mutex<HashMap<UserId, CachedUser>>>,
-    cached_variables: Arc<Mutex<HashMap<VariableId, CachedVariable>>>,
+    cached_boards: Arc<Mutex<HashMap<BoardDefinition, Cached<IndexedLeaderboard>>>>,
+    cached_games: Arc<Mutex<HashMap<GameId, Cached<Game>>>>,
+    cached_categories: Arc<Mutex<HashMap<CategoryId, Cached<Category>>>>,
+    cached_levels: Arc<Mutex<HashMap<LevelId, Cached<Level>>>>,
+    cached_users: Arc<Mutex<HashMap<UserId, Cached<User>>>>,
+    cached_variables: Arc<Mutex<HashMap<VariableId, Cached<Variable>>>>,
+    cached_latest_runs: Arc<Mutex<HashMap<UserRunsDefinition, Cached<LatestRun>>>>,
 }
 
 impl SrComBoardsState {
     pub fn new(cache_persist_time: ChronoDuration) -> SrComBoardsState {
-        let svc = tower::ServiceBuilder::new()
-            .rate_limit(100, Duration::from_secs(60))
-            .service(Client::new());
+        Self::with_base_url(cache_persist_time, SRCOM_API_URL.to_string())
+    }
+
+    /// Creates a state which sends its requests to another speedrun.com-compatible API, such as a local mock
+    pub fn with_base_url(cache_persist_time: ChronoDuration, base_url: String) -> SrComBoardsState {
+        let retry = RetryConfig::default();
 
         Self {
-            rate_limited_client: Arc::new(Mutex::new(svc)),
+            client: Self::client(&retry),
+            circuit_breaker: CircuitBreaker::new(&retry),
+            base_url,
+            cache: Arc::new(NoBoardCache),
+            refreshes: BackgroundRefreshes::default(),
+            single_flight: SingleFlight::default(),
             cache_persist_time,
             cached_boards: Arc::new(Mutex::new(HashMap::new())),
             cached_games: Arc::new(Mutex::new(HashMap::new())),
             cached_categories: Arc::new(Mutex::new(HashMap::new())),
+            cached_levels: Arc::new(Mutex::new(HashMap::new())),
             cached_users: Arc::new(Mutex::new(HashMap::new())),
-            cached_variables: Arc::new(Mutex::new(HashMap::new()))
+            cached_variables: Arc::new(Mutex::new(HashMap::new())),
+            cached_latest_runs: Arc::new(Mutex::new(HashMap::new()))
         }
     }
 
+    /// Retries throttled and failed requests according to `retry` rather than the defaults
+    pub fn with_retry(mut self, retry: RetryConfig) -> SrComBoardsState {
+        self.client = Self::client(&retry);
+        self.circuit_breaker = CircuitBreaker::new(&retry);
+        self
+    }
+
+    /// Every retry is sent through the rate limiter again, so throttled requests don't exceed the limit
+    fn client(retry: &RetryConfig) -> Retry<RetryPolicy, SharedRateLimit> {
+        tower::ServiceBuilder::new()
+            .retry(RetryPolicy::new(retry.clone()))
+            .service(SharedRateLimit::new(Client::new(), 100, Duration::from_secs(60)))
+    }
+
+    /// Keeps downloaded responses in `cache`, so they survive restarts
+    pub fn with_cache(mut self, cache: Arc<dyn BoardCache>) -> SrComBoardsState {
+        self.cache = cache;
+        self
+    }
+
     pub async fn fetch_user_highest_run(
         &self,
         user_id: UserId,
         partner_restriction: Option<PartnerRestriction>,
-        game: GameId,
-        category: CategoryId,
-        variable_map: BTreeMap<VariableId, VariableValueId>
+        board: BoardDefinition
     ) -> Result<Option<Arc<LeaderboardPlace>>, RoleManagerError> {
-        let board_definition = BoardDefinition {
-            game,
-            category,
-            level: None,
-            variables: variable_map
-        };
-
-        let leaderboard = self.fetch_leaderboard_by_definition(board_definition)
+        let leaderboard = self.fetch_leaderboard_by_definition(board)
             .await?;
 
         Ok(leaderboard.get_highest_run(user_id, partner_restriction))
     }
 
+    /// Fetches a board narrowed down by any of its variables and filters
+    pub async fn fetch_board(&self, board: BoardDefinition) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
+        self.fetch_leaderboard_by_definition(board).await
+    }
+
     pub async fn fetch_leaderboard(
         &self,
         game: GameId,
         category: CategoryId
-    ) -> Result<Arc<Leaderboard>, RoleManagerError> {
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
         self.fetch_leaderboard_by_definition(BoardDefinition {
             game,
             category,
             level: None,
-            variables: BTreeMap::new()
+            variables: BTreeMap::new(),
+            filters: BoardFilters::default()
         }).await
     }
 
@@ -119,12 +186,13 @@ impl SrComBoardsState {
         game: GameId,
         category: CategoryId,
         level: LevelId
-    ) -> Result<Arc<Leaderboard>, RoleManagerError> {
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
         self.fetch_leaderboard_by_definition(BoardDefinition {
             game,
             category,
             level: Some(level),
-            variables: BTreeMap::new()
+            variables: BTreeMap::new(),
+            filters: BoardFilters::default()
         }).await
     }
 
@@ -133,12 +201,13 @@ impl SrComBoardsState {
         game: GameId,
         category: CategoryId,
         variables: BTreeMap<VariableId, VariableValueId>
-    ) -> Result<Arc<Leaderboard>, RoleManagerError> {
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
         self.fetch_leaderboard_by_definition(BoardDefinition {
             game,
             category,
             level: None,
-            variables
+            variables,
+            filters: BoardFilters::default()
         }).await
     }
 
@@ -148,290 +217,460 @@ impl SrComBoardsState {
         category: CategoryId,
         level: LevelId,
         variables: BTreeMap<VariableId, VariableValueId>
-    ) -> Result<Arc<Leaderboard>, RoleManagerError> {
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
         self.fetch_leaderboard_by_definition(BoardDefinition {
             game,
             category,
             level: Some(level),
-            variables
+            variables,
+            filters: BoardFilters::default()
         }).await
     }
 
     async fn fetch_leaderboard_by_definition(
         &self,
         def: BoardDefinition
-    ) -> Result<Arc<Leaderboard>, RoleManagerError> {
-        let mut cached_boards = self.cached_boards.lock().await;
-        let mut cached_games = self.cached_games.lock().await;
-        let mut cached_categories = self.cached_categories.lock().await;
-        let mut cached_users = self.cached_users.lock().await;
-        let mut cached_variables = self.cached_variables.lock().await;
-
-        match cached_boards.get(&def).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_board) => {
-                Ok(Arc::clone(&cached_board.leaderboard))
-            }
-            None => {
-                let endpoint_url = match &def.level {
-                    Some(level) => Url::parse(
-                        format!("https://www.speedrun.com/api/v1/leaderboards/{}/level/{}/{}",
-                                urlencoding::encode(&def.game.0.as_str()),
-                                urlencoding::encode(level.0.as_str()),
-                                urlencoding::encode(&def.category.0.as_str())
-                        ).as_str()
-                    ),
-                    None => Url::parse(
-                        format!("https://www.speedrun.com/api/v1/leaderboards/{}/category/{}",
-                                urlencoding::encode(&def.game.0.as_str()),
-                                urlencoding::encode(&def.category.0.as_str())
-                        ).as_str()
-                    )
-                }.map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
-
-                let mut client = self.rate_limited_client.lock().await;
-                let mut request_builder = client.ready().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to obtain ticket for sending requests to speedrun.com: {}", err)))?
-                    .get_ref().get(endpoint_url)
-                    .query(&[("embed", "game,category,players,variables")]);
-
-                for var_pair in &def.variables {
-                    request_builder = request_builder.query(&[(format!("var-{}", var_pair.0.0.clone()).as_str(), var_pair.1.0.clone().as_str())]);
-                }
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
+        let key = def.cache_key();
+        if let Some(leaderboard) = self.cached_leaderboard(&def, &key).await {
+            return Ok(leaderboard);
+        }
 
-                let response: Response = request_builder.send().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)))?;
+        // Anyone else fetching this board finishes first, and has probably cached it by then
+        let _flight = self.single_flight.lock(&key).await;
 
-                let leaderboard = Arc::new(response.json::<SingleItemRequest<Leaderboard>>()
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to parse leaderboard provided by speedrun.com: {}", err)))?
-                    .data);
+        let in_memory = self.cached_boards.lock().await.contains_key(&def);
+        if !in_memory && let Some(persisted) = self.cache.load(&key).await? {
+            let leaderboard = Arc::new(IndexedLeaderboard::new(parse_item(&persisted.body, "leaderboard")?));
+            self.cache_embedded(&leaderboard.board, persisted.fetched_at).await;
+            self.cached_boards.lock().await.insert(def.clone(), Cached::new(leaderboard, persisted.fetched_at));
+        }
 
-                cached_boards.insert(def, CachedBoard {
-                    leaderboard: Arc::clone(&leaderboard),
-                    fetched_at: Utc::now().naive_utc()
-                });
+        if let Some(leaderboard) = self.cached_leaderboard(&def, &key).await {
+            return Ok(leaderboard);
+        }
 
-                // Cache any embedded information
-                if let GameOrId::Game { data } = &leaderboard.game {
-                    cached_games.insert(data.id.clone(), CachedGame {
-                        game: Arc::new(data.clone()),
-                        fetched_at: Utc::now().naive_utc()
-                    });
-                }
-                if let CategoryOrId::Category { data } = &leaderboard.category {
-                    cached_categories.insert(data.id.clone(), CachedCategory {
-                        category: Arc::new(data.clone()),
-                        fetched_at: Utc::now().naive_utc()
-                    });
-                }
-                if let Some(MultipleItemRequest { data }) = &leaderboard.players {
-                    for user in data {
-                        if let UserOrGuest::User(user) = user {
-                            cached_users.insert(user.id.clone(), CachedUser {
-                                user: Arc::new(user.clone()),
-                                fetched_at: Utc::now().naive_utc()
-                            });
-                        }
-                    }
-                }
-                if let Some(MultipleItemRequest { data }) = &leaderboard.variables {
-                    for var in data {
-                        cached_variables.insert(var.id.clone(), CachedVariable {
-                            variable: Arc::new(var.clone()),
-                            fetched_at: Utc::now().naive_utc()
-                        });
-                    }
-                }
+        let (leaderboard, body) = self.download_leaderboard(&def).await?;
+        self.remember_leaderboard(def, leaderboard, body).await
+    }
 
-                Ok(leaderboard)
+    /// The board held in memory, refreshing it in the background when it's stale
+    async fn cached_leaderboard(&self, def: &BoardDefinition, key: &str) -> Option<Arc<IndexedLeaderboard>> {
+        let cached_boards = self.cached_boards.lock().await;
+        let cached_board = cached_boards.get(def)?;
+
+        if !cached_board.is_fresh(self.cache_persist_time) {
+            // Serve the stale board while a newer one is downloaded
+            let state = self.clone();
+            let refresh_def = def.clone();
+            self.refreshes.spawn(key.to_string(), async move {
+                let (leaderboard, body) = state.download_leaderboard(&refresh_def).await?;
+                state.remember_leaderboard(refresh_def, leaderboard, body).await?;
+                Ok(())
+            });
+        }
+
+        Some(Arc::clone(&cached_board.value))
+    }
+
+    async fn download_leaderboard(&self, def: &BoardDefinition) -> Result<(Leaderboard, String), RoleManagerError> {
+        let mut endpoint_url = match &def.level {
+            Some(level) => Url::parse(
+                format!("{}/leaderboards/{}/level/{}/{}",
+                        self.base_url,
+                        urlencoding::encode(&def.game.0.as_str()),
+                        urlencoding::encode(level.0.as_str()),
+                        urlencoding::encode(&def.category.0.as_str())
+                ).as_str()
+            ),
+            None => Url::parse(
+                format!("{}/leaderboards/{}/category/{}",
+                        self.base_url,
+                        urlencoding::encode(&def.game.0.as_str()),
+                        urlencoding::encode(&def.category.0.as_str())
+                ).as_str()
+            )
+        }.map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
+
+        {
+            let mut query = endpoint_url.query_pairs_mut();
+            query.append_pair("embed", "game,category,players,variables");
+            for var_pair in &def.variables {
+                query.append_pair(format!("var-{}", var_pair.0.0).as_str(), var_pair.1.0.as_str());
+            }
+            for (name, value) in def.filters.query_pairs() {
+                query.append_pair(name, value.as_str());
             }
         }
+
+        let body = self.send(Request::new(Method::GET, endpoint_url)).await?
+            .text().await
+            .map_err(|err| RoleManagerError::new(format!("Failed to read leaderboard provided by speedrun.com: {}", err)))?;
+
+        Ok((parse_item(&body, "leaderboard")?, body))
     }
 
-    pub async fn fetch_game(&self, id: GameId) -> Result<Arc<Game>, RoleManagerError> {
-        let mut cached_games = self.cached_games.lock().await;
+    async fn remember_leaderboard(
+        &self,
+        def: BoardDefinition,
+        leaderboard: Leaderboard,
+        body: String
+    ) -> Result<Arc<IndexedLeaderboard>, RoleManagerError> {
+        let fetched_at = Utc::now().naive_utc();
+        let leaderboard = Arc::new(IndexedLeaderboard::new(leaderboard));
+
+        self.cache_embedded(&leaderboard.board, fetched_at).await;
+        self.persist_embedded(&body, fetched_at).await;
+        save_best_effort(self.cache.as_ref(), &def.cache_key(), CachedResponse { body, fetched_at }).await;
+
+        self.cached_boards.lock().await.insert(def, Cached::new(Arc::clone(&leaderboard), fetched_at));
+
+        Ok(leaderboard)
+    }
 
-        match cached_games.get(&id).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_game) => {
-                Ok(Arc::clone(&cached_game.game))
+    /// Caches the game, category, players and variables embedded in a leaderboard
+    async fn cache_embedded(&self, leaderboard: &Leaderboard, fetched_at: NaiveDateTime) {
+        if let GameOrId::Game { data } = &leaderboard.game {
+            self.cached_games.lock().await.insert(data.id.clone(), Cached::new(Arc::new(data.clone()), fetched_at));
+        }
+        if let CategoryOrId::Category { data } = &leaderboard.category {
+            self.cached_categories.lock().await.insert(data.id.clone(), Cached::new(Arc::new(data.clone()), fetched_at));
+        }
+        if let Some(MultipleItemRequest { data }) = &leaderboard.players {
+            let mut cached_users = self.cached_users.lock().await;
+            for user in data {
+                if let UserOrGuest::User(user) = user {
+                    cached_users.insert(user.id, Cached::new(Arc::new(user.clone()), fetched_at));
+                }
             }
-            None => {
-                let endpoint_url = Url::parse(
-                    format!("https://www.speedrun.com/api/v1/games/{}",
-                            urlencoding::encode(id.0.as_str())
-                    ).as_str()
-                ).map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
-
-                let mut client = self.rate_limited_client.lock().await;
-
-                let response = client.ready().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to obtain ticket for sending requests to speedrun.com: {}", err)))?
-                    .call(Request::new(Method::GET, endpoint_url))
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)))?;
-
-                let game = Arc::new(response.json::<SingleItemRequest<Game>>()
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to parse game provided by speedrun.com: {}", err)))?
-                    .data);
-
-                cached_games.insert(id.clone(), CachedGame {
-                    game: Arc::clone(&game),
-                    fetched_at: Utc::now().naive_utc()
-                });
-
-                Ok(game)
+        }
+        if let Some(MultipleItemRequest { data }) = &leaderboard.variables {
+            let mut cached_variables = self.cached_variables.lock().await;
+            for var in data {
+                cached_variables.insert(var.id.clone(), Cached::new(Arc::new(var.clone()), fetched_at));
             }
         }
     }
 
-    pub async fn fetch_category(&self, id: CategoryId) -> Result<Arc<Category>, RoleManagerError> {
-        let mut cached_categories = self.cached_categories.lock().await;
+    /// Saves the items embedded in a leaderboard response under their own keys, as if they were fetched directly
+    async fn persist_embedded(&self, body: &str, fetched_at: NaiveDateTime) {
+        let response: serde_json::Value = match serde_json::from_str(body) {
+            Ok(response) => response,
+            Err(err) => {
+                eprintln!("Failed to read the items embedded in a leaderboard: {}", err);
+                return;
+            }
+        };
+        let leaderboard = &response["data"];
 
-        match cached_categories.get(&id).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_category) => {
-                Ok(Arc::clone(&cached_category.category))
+        let mut items = Vec::new();
+        if let Some(id) = leaderboard["game"]["data"]["id"].as_str() {
+            items.push((format!("games/{}", urlencoding::encode(id)), &leaderboard["game"]["data"]));
+        }
+        if let Some(id) = leaderboard["category"]["data"]["id"].as_str() {
+            items.push((format!("categories/{}", urlencoding::encode(id)), &leaderboard["category"]["data"]));
+        }
+        for player in leaderboard["players"]["data"].as_array().into_iter().flatten() {
+            if player["rel"] == "user" && let Some(id) = player["id"].as_str() {
+                items.push((format!("users/{}", id), player));
             }
-            None => {
-                let endpoint_url = Url::parse(
-                    format!("https://www.speedrun.com/api/v1/categories/{}",
-                        urlencoding::encode(id.0.as_str())
-                    ).as_str()
-                ).map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
-
-                let mut client = self.rate_limited_client.lock().await;
-
-                let response = client.ready().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to obtain ticket for sending requests to speedrun.com: {}", err)))?
-                    .call(Request::new(Method::GET, endpoint_url))
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)))?;
-
-                let category = Arc::new(response.json::<SingleItemRequest<Category>>()
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to parse category provided by speedrun.com: {}", err)))?
-                    .data);
-
-                cached_categories.insert(id.clone(), CachedCategory {
-                    category: Arc::clone(&category),
-                    fetched_at: Utc::now().naive_utc()
-                });
-
-                Ok(category)
+        }
+        for variable in leaderboard["variables"]["data"].as_array().into_iter().flatten() {
+            if let Some(id) = variable["id"].as_str() {
+                items.push((format!("variables/{}", urlencoding::encode(id)), variable));
             }
         }
+
+        for (path, item) in items {
+            save_best_effort(self.cache.as_ref(), &format!("srcom/{}", path), CachedResponse {
+                body: serde_json::json!({ "data": item }).to_string(),
+                fetched_at
+            }).await;
+        }
+    }
+
+    pub async fn fetch_game(&self, id: GameId) -> Result<Arc<Game>, RoleManagerError> {
+        let path = format!("games/{}", urlencoding::encode(id.0.as_str()));
+        self.fetch_item(&self.cached_games, id, path, "game").await
+    }
+
+    pub async fn fetch_category(&self, id: CategoryId) -> Result<Arc<Category>, RoleManagerError> {
+        let path = format!("categories/{}", urlencoding::encode(id.0.as_str()));
+        self.fetch_item(&self.cached_categories, id, path, "category").await
+    }
+
+    pub async fn fetch_level(&self, id: LevelId) -> Result<Arc<Level>, RoleManagerError> {
+        let path = format!("levels/{}", urlencoding::encode(id.0.as_str()));
+        self.fetch_item(&self.cached_levels, id, path, "level").await
     }
 
     pub async fn fetch_user(&self, id: UserId) -> Result<Arc<User>, RoleManagerError> {
-        let mut cached_users = self.cached_users.lock().await;
+        let path = format!("users/{}", id);
+        self.fetch_item(&self.cached_users, id, path, "user").await
+    }
 
-        match cached_users.get(&id).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_user) => {
-                Ok(Arc::clone(&cached_user.user))
-            }
-            None => {
-                let endpoint_url = Url::parse(
-                    format!("https://www.speedrun.com/api/v1/users/{}", id).as_str()
-                ).map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
+    pub async fn fetch_variable(&self, id: VariableId) -> Result<Arc<Variable>, RoleManagerError> {
+        let path = format!("variables/{}", urlencoding::encode(id.0.as_str()));
+        self.fetch_item(&self.cached_variables, id, path, "variable").await
+    }
 
-                let mut client = self.rate_limited_client.lock().await;
+    /// Finds the most recent verified run a user has on a board, including obsolete runs which leaderboards leave out.
+    /// Runs are read newest first, so pages are only downloaded up to the first run on the board.
+    pub async fn fetch_latest_user_run(&self, user: UserId, board: BoardDefinition) -> Result<Option<Arc<Run>>, RoleManagerError> {
+        let def = UserRunsDefinition { user, board };
+        if let Some(cached) = self.cached_latest_runs.lock().await.get(&def) && cached.is_fresh(self.cache_persist_time) {
+            return Ok(cached.value.as_ref().clone());
+        }
 
-                let response = client.ready().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to obtain ticket for sending requests to speedrun.com: {}", err)))?
-                    .call(Request::new(Method::GET, endpoint_url))
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)))?;
+        let query = RunsQuery {
+            user: Some(user),
+            game: Some(def.board.game.clone()),
+            category: Some(def.board.category.clone()),
+            level: def.board.level.clone(),
+            verified_only: true,
+            newest_first: true
+        };
 
-                let user = Arc::new(response.json::<SingleItemRequest<User>>()
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to parse user provided by speedrun.com: {}", err)))?
-                    .data);
+        let board = &def.board;
+        let mut runs = Box::pin(self.runs(query)
+            .try_filter(|run| futures::future::ready(board.includes(run))));
+        let latest = runs.try_next().await?.map(Arc::new);
+        drop(runs);
 
-                cached_users.insert(id.clone(), CachedUser {
-                    user: Arc::clone(&user),
-                    fetched_at: Utc::now().naive_utc()
-                });
+        self.cached_latest_runs.lock().await.insert(def, Cached::new(Arc::new(latest.clone()), Utc::now().naive_utc()));
 
-                Ok(user)
-            }
+        Ok(latest)
+    }
+
+    /// Streams every run matching `query`, following the pagination links as the stream is read.
+    /// Runs aren't cached, since each page only comes up once.
+    pub fn runs(&self, query: RunsQuery) -> impl Stream<Item = Result<Run, RoleManagerError>> + Send + 'static {
+        let state = self.clone();
+        let first_page = format!("{}/{}", self.base_url, query.path());
+
+        futures::stream::try_unfold(Some(first_page), move |page_url| {
+            let state = state.clone();
+            async move { state.download_runs_page(page_url).await }
+        })
+            .map_ok(|runs| futures::stream::iter(runs.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// Downloads the runs on a page along with the url of the next page, or nothing once past the last page
+    async fn download_runs_page(&self, page_url: Option<String>) -> Result<Option<(Vec<Run>, Option<String>)>, RoleManagerError> {
+        let page_url = match page_url {
+            Some(page_url) => page_url,
+            None => return Ok(None)
+        };
+
+        let endpoint_url = Url::parse(&page_url)
+            .map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
+
+        let body = self.send(Request::new(Method::GET, endpoint_url)).await?
+            .text().await
+            .map_err(|err| RoleManagerError::new(format!("Failed to read runs provided by speedrun.com: {}", err)))?;
+
+        let page: PagedItemRequest<Run> = serde_json::from_str(&body)
+            .map_err(|err| RoleManagerError::new(format!("Failed to parse runs provided by speedrun.com: {}", err)))?;
+        let next_page = page.pagination.next_page().map(|uri| uri.to_string());
+
+        Ok(Some((page.data, next_page)))
+    }
+
+    /// Fetches a single resource such as `games/{id}`, preferring copies from memory and then from the board cache
+    async fn fetch_item<K, T>(
+        &self,
+        cached_items: &Arc<Mutex<HashMap<K, Cached<T>>>>,
+        id: K,
+        path: String,
+        kind: &'static str
+    ) -> Result<Arc<T>, RoleManagerError>
+    where
+        K: Clone + Eq + Hash + Send + Sync + 'static,
+        T: DeserializeOwned + Send + Sync + 'static
+    {
+        let key = format!("srcom/{}", path);
+        if let Some(item) = self.cached_item(cached_items, &id, &key, &path, kind).await {
+            return Ok(item);
+        }
+
+        // Anyone else fetching this item finishes first, and has probably cached it by then
+        let _flight = self.single_flight.lock(&key).await;
+
+        let in_memory = cached_items.lock().await.contains_key(&id);
+        if !in_memory && let Some(persisted) = self.cache.load(&key).await? {
+            let item = Arc::new(parse_item::<T>(&persisted.body, kind)?);
+            cached_items.lock().await.insert(id.clone(), Cached::new(item, persisted.fetched_at));
+        }
+
+        if let Some(item) = self.cached_item(cached_items, &id, &key, &path, kind).await {
+            return Ok(item);
         }
+
+        let (item, body) = self.download_item::<T>(&path, kind).await?;
+        self.remember_item(cached_items, id, item, key, body).await
     }
 
-    pub async fn fetch_variable(&self, id: VariableId) -> Result<Arc<Variable>, RoleManagerError> {
-        let mut cached_variables = self.cached_variables.lock().await;
+    /// The item held in memory, refreshing it in the background when it's stale
+    async fn cached_item<K, T>(
+        &self,
+        cached_items: &Arc<Mutex<HashMap<K, Cached<T>>>>,
+        id: &K,
+        key: &str,
+        path: &str,
+        kind: &'static str
+    ) -> Option<Arc<T>>
+    where
+        K: Clone + Eq + Hash + Send + Sync + 'static,
+        T: DeserializeOwned + Send + Sync + 'static
+    {
+        let items = cached_items.lock().await;
+        let cached_item = items.get(id)?;
+
+        if !cached_item.is_fresh(self.cache_persist_time) {
+            // Serve the stale item while a newer one is downloaded
+            let state = self.clone();
+            let cached_items = Arc::clone(cached_items);
+            let id = id.clone();
+            let refresh_key = key.to_string();
+            let path = path.to_string();
+            self.refreshes.spawn(key.to_string(), async move {
+                let (item, body) = state.download_item::<T>(&path, kind).await?;
+                state.remember_item(&cached_items, id, item, refresh_key, body).await?;
+                Ok(())
+            });
+        }
 
-        match cached_variables.get(&id).filter(|c| {
-            c.fetched_at.checked_add_signed(self.cache_persist_time).map(|t| t > Utc::now().naive_utc()).unwrap_or(false)
-        }) {
-            Some(cached_variable) => {
-                Ok(Arc::clone(&cached_variable.variable))
-            }
-            None => {
-                let endpoint_url = Url::parse(
-                    format!("https://www.speedrun.com/api/v1/variables/{}",
-                        urlencoding::encode(id.0.as_str())
-                    ).as_str()
-                ).map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
-
-                let mut client = self.rate_limited_client.lock().await;
-
-                let response = client.ready().await
-                    .map_err(|err| RoleManagerError::new(format!("Failed to obtain ticket for sending requests to speedrun.com: {}", err)))?
-                    .call(Request::new(Method::GET, endpoint_url))
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)))?;
-
-                let variable = Arc::new(response.json::<SingleItemRequest<Variable>>()
-                    .await.map_err(|err| RoleManagerError::new(format!("Failed to parse variable provided by speedrun.com: {}", err)))?
-                    .data);
-
-                cached_variables.insert(id.clone(), CachedVariable {
-                    variable: Arc::clone(&variable),
-                    fetched_at: Utc::now().naive_utc()
-                });
-
-                Ok(variable)
+        Some(Arc::clone(&cached_item.value))
+    }
+
+    async fn download_item<T: DeserializeOwned>(&self, path: &str, kind: &str) -> Result<(T, String), RoleManagerError> {
+        let endpoint_url = Url::parse(
+            format!("{}/{}", self.base_url, path).as_str()
+        ).map_err(|err| RoleManagerError::new(format!("Failed to build API request to speedrun.com: {}", err)))?;
+
+        let body = self.send(Request::new(Method::GET, endpoint_url)).await?
+            .text().await
+            .map_err(|err| RoleManagerError::new(format!("Failed to read {} provided by speedrun.com: {}", kind, err)))?;
+
+        Ok((parse_item(&body, kind)?, body))
+    }
+
+    async fn remember_item<K: Eq + Hash, T>(
+        &self,
+        cached_items: &Mutex<HashMap<K, Cached<T>>>,
+        id: K,
+        item: T,
+        key: String,
+        body: String
+    ) -> Result<Arc<T>, RoleManagerError> {
+        let fetched_at = Utc::now().naive_utc();
+        let item = Arc::new(item);
+
+        save_best_effort(self.cache.as_ref(), &key, CachedResponse { body, fetched_at }).await;
+        cached_items.lock().await.insert(id, Cached::new(Arc::clone(&item), fetched_at));
+
+        Ok(item)
+    }
+
+    /// Sends a request, retrying while speedrun.com throttles or fails, and reports unsuccessful responses as errors
+    async fn send(&self, request: Request) -> Result<Response, RoleManagerError> {
+        self.circuit_breaker.check("speedrun.com")?;
+
+        let response = match self.client.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(err) => {
+                self.circuit_breaker.record_failure();
+                return Err(RoleManagerError::new(format!("Failed to send request to speedrun.com: {}", err)));
             }
+        };
+
+        let status = response.status();
+        if is_retryable(status) {
+            self.circuit_breaker.record_failure();
+        } else {
+            self.circuit_breaker.record_success();
         }
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        // speedrun.com explains what went wrong in the body, which is more useful than failing to parse it
+        let url = response.url().to_string();
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|error| error.message)
+            .unwrap_or(body);
+
+        Err(RoleManagerError::new(format!("speedrun.com responded to {} with {}: {}", url, status, message.trim())))
     }
 }
 
-#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
-struct BoardDefinition {
-    game: GameId,
-    category: CategoryId,
-    level: Option<LevelId>,
-    variables: BTreeMap<VariableId, VariableValueId>
+/// Parses a response from speedrun.com holding a single item
+fn parse_item<T: DeserializeOwned>(body: &str, kind: &str) -> Result<T, RoleManagerError> {
+    Ok(serde_json::from_str::<SingleItemRequest<T>>(body)
+        .map_err(|err| RoleManagerError::new(format!("Failed to parse {} provided by speedrun.com: {}", kind, err)))?
+        .data)
 }
 
-#[derive(Debug)]
-struct CachedBoard {
-    leaderboard: Arc<Leaderboard>,
-    fetched_at: NaiveDateTime
+/// A leaderboard as requested from speedrun.com
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct BoardDefinition {
+    pub game: GameId,
+    pub category: CategoryId,
+    pub level: Option<LevelId>,
+    pub variables: BTreeMap<VariableId, VariableValueId>,
+    pub filters: BoardFilters
 }
 
-#[derive(Debug)]
-struct CachedGame {
-    game: Arc<Game>,
-    fetched_at: NaiveDateTime
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct UserRunsDefinition {
+    user: UserId,
+    board: BoardDefinition
 }
 
-#[derive(Debug)]
-struct CachedCategory {
-    category: Arc<Category>,
-    fetched_at: NaiveDateTime
-}
+impl BoardDefinition {
+    /// Whether a dated, verified run from the runs endpoint belongs on this board. The endpoint doesn't filter by
+    /// variables or the board's filters, and includes level runs when no level is given.
+    pub fn includes(&self, run: &Run) -> bool {
+        run.date.is_some()
+            && matches!(run.status, RunStatus::Verified { .. })
+            && run.game == self.game
+            && run.category == self.category
+            && run.level == self.level
+            && self.variables.iter().all(|(variable, value)| run.values.get(variable) == Some(value))
+            && self.filters.matches(run)
+    }
 
-#[derive(Debug)]
-struct CachedUser {
-    user: Arc<User>,
-    fetched_at: NaiveDateTime
+    fn cache_key(&self) -> String {
+        let variables: Vec<String> = self.variables.iter()
+            .map(|(variable, value)| format!("{}={}", variable.0, value.0))
+            .collect();
+
+        let key = format!("srcom/leaderboards/{}/{}/{}/{}",
+                self.game.0,
+                self.category.0,
+                self.level.as_ref().map(|level| level.0.as_str()).unwrap_or("-"),
+                variables.join(",")
+        );
+
+        // Filtered boards get keys of their own, while unfiltered ones keep the keys they always had
+        let filters: Vec<String> = self.filters.query_pairs().iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if filters.is_empty() {
+            key
+        } else {
+            format!("{}/{}", key, filters.join(","))
+        }
+    }
 }
 
-#[derive(Debug)]
-struct CachedVariable {
-    variable: Arc<Variable>,
-    fetched_at: NaiveDateTime
+/// The body speedrun.com sends along with an unsuccessful status
+#[derive(Deserialize, Debug, Clone)]
+struct ErrorResponse {
+    message: String
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -443,3 +682,26 @@ pub struct SingleItemRequest<T> {
 pub struct MultipleItemRequest<T> {
     data: Vec<T>
 }
+
+/// One page of a response listing many items, linking to the next page if there is one
+#[derive(Deserialize, Debug, Clone)]
+pub struct PagedItemRequest<T> {
+    data: Vec<T>,
+    pagination: Pagination
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Pagination {
+    pub offset: u64,
+    pub max: u64,
+    pub size: u64,
+    pub links: Vec<Link>
+}
+
+impl Pagination {
+    pub fn next_page(&self) -> Option<&str> {
+        self.links.iter()
+            .find(|link| link.rel == "next")
+            .map(|link| link.uri.as_str())
+    }
+}
@@ -3,7 +3,7 @@ use serde::Deserialize;
 use crate::boards::srcom::category::CategoryId;
 use crate::boards::srcom::game::GameId;
 use crate::boards::srcom::level::LevelId;
-use crate::boards::srcom::Link;
+use crate::boards::srcom::{Link, TimingMethod};
 use crate::boards::srcom::platform::PlatformId;
 use crate::boards::srcom::region::RegionId;
 use crate::boards::srcom::user::UserId;
@@ -88,6 +88,19 @@ pub struct RunTimes {
     pub ingame_t: f64
 }
 
+impl RunTimes {
+    /// The run's time in seconds by `timing`, or its primary time without one. Runs which weren't timed
+    /// that way have no time.
+    pub fn seconds(&self, timing: Option<TimingMethod>) -> Option<f64> {
+        match timing {
+            None => Some(self.primary_t),
+            Some(TimingMethod::RealTime) => self.realtime.as_ref().map(|_| self.realtime_t),
+            Some(TimingMethod::RealTimeNoLoads) => self.realtime_noloads.as_ref().map(|_| self.realtime_noloads_t),
+            Some(TimingMethod::InGame) => self.ingame.as_ref().map(|_| self.ingame_t)
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct RunSystem {
     pub platform: Option<PlatformId>,
@@ -103,3 +116,55 @@ pub enum RunSplits {
         uri: String
     }
 }
+
+/// Filters for the `/runs` endpoint. Unlike leaderboards, its results include obsolete runs.
+#[derive(Debug, Clone, Default)]
+pub struct RunsQuery {
+    pub user: Option<UserId>,
+    pub game: Option<GameId>,
+    pub category: Option<CategoryId>,
+    pub level: Option<LevelId>,
+    pub verified_only: bool,
+    /// Orders runs by the date they were done, newest first, instead of by submission
+    pub newest_first: bool
+}
+
+impl RunsQuery {
+    pub fn for_user(user: UserId) -> Self {
+        Self { user: Some(user), ..Default::default() }
+    }
+
+    pub fn for_game(game: GameId) -> Self {
+        Self { game: Some(game), ..Default::default() }
+    }
+
+    pub fn for_category(game: GameId, category: CategoryId) -> Self {
+        Self { game: Some(game), category: Some(category), ..Default::default() }
+    }
+
+    /// Path of the first page of runs, relative to the API's base url
+    pub fn path(&self) -> String {
+        let mut params = vec![];
+        if let Some(user) = &self.user {
+            params.push(format!("user={}", user));
+        }
+        if let Some(game) = &self.game {
+            params.push(format!("game={}", urlencoding::encode(game.0.as_str())));
+        }
+        if let Some(category) = &self.category {
+            params.push(format!("category={}", urlencoding::encode(category.0.as_str())));
+        }
+        if let Some(level) = &self.level {
+            params.push(format!("level={}", urlencoding::encode(level.0.as_str())));
+        }
+        if self.verified_only {
+            params.push("status=verified".to_string());
+        }
+        if self.newest_first {
+            params.push("orderby=date&direction=desc".to_string());
+        }
+        params.push("max=200".to_string());
+
+        format!("runs?{}", params.join("&"))
+    }
+}
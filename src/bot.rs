@@ -6,10 +6,6 @@ use itertools::Itertools;
 
 use poise::{CreateReply, serenity_prelude as serenity};
 
-use sea_orm::DatabaseConnection;
-use sea_orm::EntityTrait;
-use sea_orm::QueryFilter;
-use sea_orm::ColumnTrait;
 use serenity::all::{CreateEmbed, Http};
 use serenity::builder::CreateAllowedMentions;
 use serenity::model::prelude::*;
@@ -18,22 +14,38 @@ use crate::analyzer;
 use crate::boards::cm::CmBoardsState;
 use crate::boards::srcom::SrComBoardsState;
 use crate::error::RoleManagerError;
-use crate::analyzer::role_definition::{BadgeDefinition, RoleDefinition};
-use crate::analyzer::user;
+use crate::analyzer::role_definition::RoleDefinition;
 use crate::analyzer::user::{analyze_user, ExternalAccount};
+use crate::analyzer::compare::compare_definitions;
+use crate::analyzer::pipeline;
+use crate::analyzer::whatif::{sweep_requirement, ThresholdParameter, ThresholdSweep};
 use crate::config::Config;
 use crate::model::lumadb::{manual_role_assignments, verified_connections};
+use crate::roles::{MemberRoles, RoleChange, RoleChangeKind};
+use crate::roles::executor::{execute_plan, AuditLogExecutor, DiscordExecutor, DryRunExecutor};
+use crate::roles::notifications::notify_changes;
+use crate::roles::planner::plan_role_changes;
+use crate::server;
 use crate::server::ServerConfig;
+use crate::store::Store;
 
 #[derive(Debug)]
 pub struct BotState {
-    pub(crate) db: Arc<DatabaseConnection>,
+    pub(crate) store: Arc<dyn Store>,
     pub(crate) srcom_state: SrComBoardsState,
-    pub(crate) cm_state: CmBoardsState
+    pub(crate) cm_state: CmBoardsState,
+    pub(crate) analysis_guild: Option<GuildId>,
+    pub(crate) analysis_workers: usize
 }
 
 type PoiseContext<'a> = poise::Context<'a, BotState, RoleManagerError>;
 
+/// The guild whose members are analyzed, which has to be set in the config for commands analyzing every member
+fn analysis_guild(ctx: PoiseContext<'_>) -> Result<GuildId, RoleManagerError> {
+    ctx.data().analysis_guild
+        .ok_or_else(|| RoleManagerError::new_edit("No analysis_guild is configured for this bot".to_string()))
+}
+
 async fn on_error(error: poise::FrameworkError<'_, BotState, RoleManagerError>) {
     match error {
         poise::FrameworkError::Command { error , ctx, .. } => {
@@ -53,32 +65,35 @@ async fn on_error(error: poise::FrameworkError<'_, BotState, RoleManagerError>)
     }
 }
 
-pub async fn create_bot(config: Config, db: Arc<DatabaseConnection>, srcom_state: SrComBoardsState, cm_state: CmBoardsState) -> Result<(), RoleManagerError> {
+pub async fn create_bot(config: Config, store: Arc<dyn Store>, srcom_state: SrComBoardsState, cm_state: CmBoardsState) -> Result<(), RoleManagerError> {
 
-    let db2 = Arc::clone(&db);
+    let store2 = Arc::clone(&store);
     let srcom_state2 = srcom_state.clone();
     let cm_state2 = cm_state.clone();
+    let command_guilds = config.command_guilds.clone();
+    if command_guilds.is_empty() {
+        eprintln!("Warning: No command_guilds configured, slash commands won't be registered anywhere.");
+    }
+    let analysis_guild = config.analysis_guild.map(GuildId::new);
+    if analysis_guild.is_none() {
+        eprintln!("Warning: No analysis_guild configured, commands analyzing every member are unavailable.");
+    }
+    let analysis_workers = config.analysis_workers;
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![server(), analyze(), user(), generate_report()],
+            commands: vec![server(), analyze(), user(), progress(), generate_report(), whatif(), compare()],
             on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
         .setup(move |ctx,_ready, framework| Box::pin(async move {
-            GuildId::new(146404426746167296).set_commands(ctx,
-                poise::builtins::create_application_commands(&framework.options().commands)
-            ).await.unwrap();
-
-            GuildId::new(299658323500990464).set_commands(ctx,
-                poise::builtins::create_application_commands(&framework.options().commands)
-            ).await.unwrap();
-
-            GuildId::new(713630719582404609).set_commands(ctx,
-                poise::builtins::create_application_commands(&framework.options().commands)
-            ).await.unwrap();
+            for guild_id in &command_guilds {
+                GuildId::new(*guild_id).set_commands(ctx,
+                    poise::builtins::create_application_commands(&framework.options().commands)
+                ).await?;
+            }
 
-            Ok(BotState { db, srcom_state, cm_state })
+            Ok(BotState { store, srcom_state, cm_state, analysis_guild, analysis_workers })
         }))
         .build();
 
@@ -94,12 +109,18 @@ pub async fn create_bot(config: Config, db: Arc<DatabaseConnection>, srcom_state
         std::process::exit(-1);
     });
 
-    // Start a loop updating badges in P2SR every 5 minutes
+    // Start a loop updating badges in every configured server
     tokio::spawn(async move {
         loop {
-            let guild_id = GuildId::new(146404426746167296);
-            if let Err(e) = update_badge_roles(guild_id, &db2, &http, &srcom_state2, &cm_state2).await {
-                eprintln!("Encountered error while updating badge roles:\n{:?}", e);
+            match ServerConfig::configured_servers().await {
+                Ok(servers) => {
+                    for server_id in servers {
+                        if let Err(e) = update_badge_roles(GuildId::new(server_id), store2.as_ref(), &http, &srcom_state2, &cm_state2, analysis_workers, false).await {
+                            eprintln!("Encountered error while updating badge roles in server {}:\n{:?}", server_id, e);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Failed to list configured servers:\n{:?}", e)
             }
 
             tokio::select! {
@@ -114,158 +135,135 @@ pub async fn create_bot(config: Config, db: Arc<DatabaseConnection>, srcom_state
     Ok(())
 }
 
-async fn update_badge_roles(guild_id: GuildId, db: &DatabaseConnection, client: &Http, srcom_state: &SrComBoardsState, cm_state: &CmBoardsState) -> Result<(), RoleManagerError> {
+/// Updates the badge roles of every member in a server, returning the changes which were planned.
+/// With `preview` set, no roles are changed regardless of the server's `dryrun` setting.
+async fn update_badge_roles(guild_id: GuildId, store: &dyn Store, client: &Http, srcom_state: &SrComBoardsState, cm_state: &CmBoardsState, workers: usize, preview: bool) -> Result<Vec<RoleChange>, RoleManagerError> {
     println!("Updating badge roles for server {}...", guild_id);
 
     let server_config = match ServerConfig::read(guild_id.get()).await? {
         Some(config) => config,
         None => {
-            eprintln!("Warning: Default server {} doesn't have a set configuration.", guild_id.get());
-            return Ok(());
+            eprintln!("Warning: Server {} doesn't have a set configuration.", guild_id.get());
+            return Ok(vec![]);
         }
     };
-    let definition_path = format!("server_definitions/{}.json5", guild_id.get());
-
-    if tokio::fs::try_exists(&definition_path).await? {
-        let definition_content = tokio::fs::read_to_string(&definition_path).await?;
-        let definition = json5::from_str(&definition_content)?;
-        let valid_badges = server_config.valid_badges(&definition);
-        let valid_completed_badges = server_config.valid_completed_badges(&definition);
-
-        let badge_set: HashSet<&BadgeDefinition> = valid_badges.keys().into_iter()
-            .map(|x| *x)
-            .collect();
-        let completed_badge_set: HashSet<&BadgeDefinition> = valid_completed_badges.keys().into_iter()
-            .map(|x| *x)
-            .collect();
-
-        let connections: Vec<verified_connections::Model> = verified_connections::Entity::find()
-            .filter(verified_connections::Column::Removed.eq(0))
-            .all(db).await?;
-
-        let manual_assignments: Vec<manual_role_assignments::Model> = manual_role_assignments::Entity::find()
-            .all(db).await?;
-
-        let mut members = guild_id.members_iter(client).boxed();
-        while let Some(member) = members.next().await {
-            let member = member?;
-
-            let analysis = user::analyze_user(
-                member.user.id.get(),
-                &definition,
-                &connections,
-                srcom_state.clone(),
-                cm_state.clone(),
-                false
-            ).await?;
+    let definition = match server::read_definition(guild_id.get()).await? {
+        Some(definition) => definition,
+        None => return Ok(vec![])
+    };
 
-            let mut badges_to_remove = badge_set.clone();
-            let mut complete_badges_to_remove = completed_badge_set.clone();
+    let connections: Vec<verified_connections::Model> = store.connections().await?;
 
-            // Make sure the user has roles that they are supposed to
-            for analyzed_badge in analysis.badges {
-                if let Some(role_id) = valid_badges.get(&analyzed_badge.definition) {
-                    let role_id = RoleId::new(*role_id);
+    let manual_assignments: Vec<manual_role_assignments::Model> = store.manual_assignments(guild_id.get()).await?;
 
-                    if !member.roles.contains(&role_id) {
-                        let short_reason = analyzed_badge.met_requirements.iter()
-                            .map(|r| r.definition.short_description())
-                            .join(", ");
+    let mut members = Vec::new();
 
-                        println!("Trying to add role {} to user {}", analyzed_badge.definition.name, member.display_name());
-                        println!(" - {}", short_reason);
+    let mut member_stream = guild_id.members_iter(client).boxed();
+    while let Some(member) = member_stream.next().await {
+        let member = member?;
 
-                        if !&server_config.dry_run {
-                            client.add_member_role(guild_id, member.user.id, role_id, Some(&short_reason)).await?
-                        }
-                    }
-                }
-                badges_to_remove.remove(analyzed_badge.definition);
+        members.push(MemberRoles {
+            user_id: member.user.id.get(),
+            name: member.display_name().to_string(),
+            roles: member.roles.iter().map(|role| role.get()).collect()
+        });
+    }
 
-                // Check if completed badge should also be awarded
-                if analyzed_badge.is_complete() {
-                    if let Some(role_id) = valid_completed_badges.get(&analyzed_badge.definition) {
-                        let role_id = RoleId::new(*role_id);
+    let user_ids: Vec<u64> = members.iter().map(|member| member.user_id).collect();
+    let mut analyses = pipeline::analyze_users(&definition, &connections, &user_ids, srcom_state, cm_state, workers).await?;
 
-                        if !member.roles.contains(&role_id) {
-                            let short_reason = analyzed_badge.met_requirements.iter()
-                                .map(|r| r.definition.short_description())
-                                .join(", ");
+    let mut plan = plan_role_changes(&definition, &server_config, &members, &analyses, &manual_assignments);
 
-                            println!("Trying to add *completed* role {} to user {}", analyzed_badge.definition.name, member.display_name());
-                            println!(" - {}", short_reason);
+    // Gaps are only worked out for members losing a role, so the change log can explain why they lost it
+    let losing_roles: HashSet<u64> = plan.iter()
+        .filter(|change| change.kind == RoleChangeKind::Remove)
+        .map(|change| change.user_id)
+        .collect();
+    if !losing_roles.is_empty() {
+        for analysis in analyses.iter_mut().filter(|analysis| losing_roles.contains(&analysis.discord_id)) {
+            if let Err(err) = analysis.compute_gaps(srcom_state, cm_state).await {
+                eprintln!("Failed to work out why user {} loses roles in server {}: {}", analysis.discord_id, guild_id, err);
+            }
+        }
+        plan = plan_role_changes(&definition, &server_config, &members, &analyses, &manual_assignments);
+    }
 
-                            if !server_config.dry_run {
-                                client.add_member_role(guild_id, member.user.id, role_id, Some(&short_reason)).await?
-                            }
-                        }
-                    }
+    if server_config.dry_run || preview {
+        execute_plan(guild_id.get(), &plan, &DryRunExecutor).await?;
+    } else {
+        let executor = AuditLogExecutor {
+            inner: DiscordExecutor { http: client },
+            log: store,
+            definition_version: server::definition_version(guild_id.get()).await?
+        };
+        execute_plan(guild_id.get(), &plan, &executor).await?;
 
-                    complete_badges_to_remove.remove(analyzed_badge.definition);
-                }
-            }
+        // The roles have already changed by now, so a channel which can't be posted in shouldn't fail the refresh
+        if let Err(err) = notify_changes(client, &server_config, &plan).await {
+            eprintln!("Failed to post role change notifications in server {}: {}", guild_id, err);
+        }
+    }
 
-            // Make sure the user doesn't have roles they're not supposed to
-            for badge_definition in badges_to_remove {
-                if !badge_definition.can_autoremove() {
-                    continue;
-                }
+    Ok(plan)
+}
 
-                if let Some(role_id) = valid_badges.get(badge_definition) {
-                    let role_id = RoleId::new(*role_id);
-
-                    if member.roles.contains(&role_id) {
-                        // See if this role is manually assigned
-                        let mut manually_assigned = false;
-                        for assignment in &manual_assignments {
-                            if (*(&assignment.user_id) as u64) == member.user.id.get() && (*(&assignment.role_id) as u64) == role_id.get() {
-                                manually_assigned = true;
-                                break;
-                            }
-                        }
-                        if !manually_assigned {
-                            println!("Trying to remove role {} from user {}", badge_definition.name, member.display_name());
+/// Downloads a definition file attached to a command
+async fn download_definition(definition_file: &Attachment) -> Result<String, RoleManagerError> {
+    reqwest::get(definition_file.url.clone())
+        .await.map_err(|err| RoleManagerError::new_edit(format!("Failed to download provided role definition file: {}", err)))?
+        .text().await.map_err(|err| RoleManagerError::new_edit(format!("Failed to interpret provided role definition file download: {}", err)))
+}
 
-                            if !&server_config.dry_run {
-                                client.remove_member_role(guild_id, member.user.id, role_id, None).await?
-                            }
-                        }
-                    }
+/// The definition attached to a command, or else the definition of the server it was used in, along with its contents
+/// and file name. Replies explaining why when neither is available, returning nothing.
+async fn load_definition(ctx: PoiseContext<'_>, definition_file: Option<Attachment>) -> Result<Option<(RoleDefinition, String, String)>, RoleManagerError> {
+    let (response_str, definition_filename): (String, String) = match definition_file {
+        Some(definition_file) => (download_definition(&definition_file).await?, definition_file.filename),
+        None => {
+            if let Some(guild_id) = ctx.guild_id() {
+                // Use this server's definition file
+                let definition_path = format!("server_definitions/{}.json5", guild_id.get());
+
+                if tokio::fs::try_exists(&definition_path).await? {
+                    (tokio::fs::read_to_string(&definition_path).await?, format!("{}.json5", guild_id.get()))
+                } else {
+                    ctx.reply("This server doesn't have a definition file set for it! Try attatching one.").await?;
+                    return Ok(None)
                 }
+            } else {
+                ctx.reply("This command can only be run in servers.").await?;
+                return Ok(None)
             }
+        }
+    };
 
-            // Make sure the user doesn't have completed roles they're not supposed to
-            for badge_definition in complete_badges_to_remove {
-                if !badge_definition.can_autoremove() {
-                    continue;
-                }
+    let definition: RoleDefinition = json5::from_str(&response_str)
+        .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
 
-                if let Some(role_id) = valid_completed_badges.get(badge_definition) {
-                    let role_id = RoleId::new(*role_id);
-
-                    if member.roles.contains(&role_id) {
-                        // See if this role is manually assigned
-                        let mut manually_assigned = false;
-                        for assignment in &manual_assignments {
-                            if (*(&assignment.user_id) as u64) == member.user.id.get() && (*(&assignment.role_id) as u64) == role_id.get() {
-                                manually_assigned = true;
-                                break;
-                            }
-                        }
-                        if !manually_assigned {
-                            println!("Trying to remove *completed* role {} from user {}", badge_definition.name, member.display_name());
+    Ok(Some((definition, response_str, definition_filename)))
+}
 
-                            if !&server_config.dry_run {
-                                client.remove_member_role(guild_id, member.user.id, role_id, None).await?
-                            }
-                        }
-                    }
-                }
-            }
+/// Every member of a guild, requested a thousand at a time
+async fn fetch_all_members(http: &Http, guild_id: GuildId) -> Result<Vec<Member>, RoleManagerError> {
+    let mut members: Vec<Member> = Vec::new();
+    let mut offset: Option<u64> = None;
+
+    loop {
+        let iteration = http.get_guild_members(guild_id, Some(1_000), offset).await?;
+        if !iteration.is_empty() {
+            offset = iteration.last().map(|member| member.user.id.get());
+        }
+
+        let end = iteration.len() < 1000;
+
+        members.extend(iteration);
+
+        if end {
+            break;
         }
     }
 
-    Ok(())
+    Ok(members)
 }
 
 async fn autocomplete_badge<'a>(ctx: PoiseContext<'_>, partial: &'a str) -> impl Stream<Item = String> + 'a {
@@ -288,7 +286,7 @@ async fn autocomplete_badge<'a>(ctx: PoiseContext<'_>, partial: &'a str) -> impl
 }
 
 /// Manage skill roles in this server
-#[poise::command(slash_command, required_permissions = "MANAGE_GUILD", subcommands("redefine", "roles", "refresh", "dryrun"))]
+#[poise::command(slash_command, required_permissions = "MANAGE_GUILD", subcommands("redefine", "validate", "roles", "refresh", "preview", "history", "dryrun", "notifications"))]
 async fn server(_ctx: PoiseContext<'_>) -> Result<(), RoleManagerError> {
     Err(RoleManagerError::new("Impossible state reached, cannot run menu commands".to_string()))
 }
@@ -478,10 +476,52 @@ async fn redefine(
     if let Some(server_id) = ctx.guild_id() {
         let def_contents = definition_file.download().await?;
 
-        tokio::fs::create_dir_all("server_definitions").await?;
-        tokio::fs::write(format!("server_definitions/{}.json5", server_id.get()), def_contents).await?;
+        let definition_str = String::from_utf8(def_contents.clone())
+            .map_err(|err| RoleManagerError::new_edit(format!("Definition file is not valid UTF-8: {}", err)))?;
+        let definition: RoleDefinition = json5::from_str(&definition_str)
+            .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
+
+        // Refuse to replace the current definition with one that would break role updates
+        let report = definition.validate(&ctx.data().srcom_state).await;
+        if !report.is_valid() {
+            ctx.send(poise::CreateReply::default()
+                .content(format!("Definition was **not** updated, found {} problems.", report.problem_count()))
+                .attachment(serenity::CreateAttachment::bytes(report.to_string().into_bytes(), "validation.txt"))
+            ).await?;
+            return Ok(());
+        }
+
+        // A previous definition that no longer parses is treated as if there was none
+        let previous_definition = server::read_definition(server_id.get()).await.unwrap_or(None);
+        let backup_path = server::replace_definition(server_id.get(), &def_contents).await?;
+
+        let diff = previous_definition
+            .unwrap_or(RoleDefinition { badges: vec![] })
+            .diff(&definition);
+        let unmatched_roles = ServerConfig::read(server_id.get()).await?
+            .map(|config| config.unmatched_badge_roles(&definition))
+            .unwrap_or_default();
 
-        ctx.reply("Updated definitions file for this server!").await?;
+        let list_or_none = |names: &Vec<String>| if names.is_empty() {
+            "*None*".to_string()
+        } else {
+            names.iter().map(|name| format!("- {}", name)).join("\n")
+        };
+
+        let mut embed = CreateEmbed::new()
+            .title("Updated definitions file for this server!")
+            .field("Added Badges", list_or_none(&diff.added), false)
+            .field("Removed Badges", list_or_none(&diff.removed), false)
+            .field("Changed Badges", list_or_none(&diff.changed), false)
+            .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_file.filename)));
+        if !unmatched_roles.is_empty() {
+            embed = embed.field("Roles Without a Badge", list_or_none(&unmatched_roles), false);
+        }
+        if let Some(backup_path) = backup_path {
+            embed = embed.description(format!("Previous definition kept as `{}`", backup_path));
+        }
+
+        ctx.send(poise::CreateReply::default().embed(embed)).await?;
     } else {
         ctx.reply("Can only use command on servers!").await?;
     }
@@ -489,6 +529,44 @@ async fn redefine(
     Ok(())
 }
 
+/// Check a skill role definition for mistakes without applying it
+#[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
+async fn validate(
+    ctx: PoiseContext<'_>,
+    #[description = "Json5 file describing skill role definitions"]
+    definition_file: Attachment
+) -> Result<(), RoleManagerError> {
+    println!("Deferring response");
+    ctx.defer().await?;
+    println!("Finished deferring response");
+
+    let response = download_definition(&definition_file).await?;
+
+    let definition: RoleDefinition = json5::from_str(&response)
+        .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
+
+    let report = definition.validate(&ctx.data().srcom_state).await;
+
+    let description = if report.is_valid() {
+        format!("No problems found in **{} badges** ({} requirements)", report.badges.len(), report.requirement_count())
+    } else {
+        format!("Found **{} problems** in **{} badges** ({} requirements)", report.problem_count(), report.badges.len(), report.requirement_count())
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Definition Validation")
+        .description(description)
+        .color(if report.is_valid() { Color::DARK_GREEN } else { Color::RED })
+        .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_file.filename)));
+
+    ctx.send(poise::CreateReply::default()
+        .embed(embed)
+        .attachment(serenity::CreateAttachment::bytes(report.to_string().into_bytes(), "validation.txt"))
+    ).await?;
+
+    Ok(())
+}
+
 /// Manually trigger a role assignments refresh
 #[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
 async fn refresh(
@@ -500,7 +578,7 @@ async fn refresh(
 
     let response = match ctx.guild_id() {
         Some(guild_id) => {
-            update_badge_roles(guild_id, &ctx.data().db, ctx.http(), &(ctx.data().srcom_state.clone()), &(ctx.data().cm_state.clone())).await?;
+            update_badge_roles(guild_id, ctx.data().store.as_ref(), ctx.http(), &(ctx.data().srcom_state.clone()), &(ctx.data().cm_state.clone()), ctx.data().analysis_workers, false).await?;
 
             "Updated badge in servers".to_string()
         }
@@ -514,6 +592,117 @@ async fn refresh(
     Ok(())
 }
 
+/// Lists the role changes a refresh would make, without changing any roles
+#[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
+async fn preview(
+    ctx: PoiseContext<'_>
+) -> Result<(), RoleManagerError> {
+    println!("Deferring response");
+    ctx.defer().await?;
+    println!("Finished deferring response");
+
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            ctx.reply("Can only use command on servers!").await?;
+            return Ok(());
+        }
+    };
+
+    let changes = update_badge_roles(guild_id, ctx.data().store.as_ref(), ctx.http(), &(ctx.data().srcom_state.clone()), &(ctx.data().cm_state.clone()), ctx.data().analysis_workers, true).await?;
+
+    let mut report = csv::Writer::from_writer(vec![]);
+    report.write_record(["Discord User", "Action", "Badge", "Role", "Reason"])
+        .map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+    for change in &changes {
+        report.write_record([
+            change.user_name.as_str(),
+            change.kind.to_string().as_str(),
+            change.badge.as_str(),
+            if change.completed { "completed" } else { "base" },
+            change.reason.as_str()
+        ]).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+    }
+    let report_inner = report.into_inner()
+        .map_err(|e| RoleManagerError::new(format!("Failed to generate report: {}", e)))?;
+
+    let additions = changes.iter().filter(|change| change.kind == RoleChangeKind::Add).count();
+    let embed = CreateEmbed::new()
+        .title("Role Change Preview")
+        .description(format!("A refresh would make **{} additions** and **{} removals**", additions, changes.len() - additions));
+
+    ctx.send(poise::CreateReply::default()
+        .embed(embed)
+        .attachment(serenity::CreateAttachment::bytes(report_inner, "preview.csv"))
+    ).await?;
+
+    Ok(())
+}
+
+/// Lists the badge roles the bot has added to or removed from a user
+#[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
+async fn history(
+    ctx: PoiseContext<'_>,
+    #[description = "User to list role changes for"]
+    user: User,
+    #[description = "Page of changes to show, starting from the most recent"]
+    #[min = 1]
+    page: Option<u64>
+) -> Result<(), RoleManagerError> {
+    println!("Deferring response");
+    ctx.defer().await?;
+    println!("Finished deferring response");
+
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            ctx.reply("Can only use command on servers!").await?;
+            return Ok(());
+        }
+    };
+
+    let store = ctx.data().store.as_ref();
+    let page_count = store.change_history_pages(guild_id.get(), user.id.get(), 10).await?;
+    let page = page.unwrap_or(1).clamp(1, page_count.max(1));
+    let entries = store.change_history_page(guild_id.get(), user.id.get(), 10, page - 1).await?;
+
+    let mut description = String::new();
+    for entry in &entries {
+        let added = entry.direction == RoleChangeKind::Add.to_string();
+        let symbol = if added { "+" } else { "-" };
+        let completed = if entry.completed != 0 { " (completed)" } else { "" };
+
+        writeln!(&mut description, "`{}` **{}{}**{} <@&{}>", entry.changed_at.format("%Y-%m-%d %H:%M"), symbol, entry.badge, completed, entry.role_id)?;
+
+        // Additions list what was met as their causes, while removals need their reason to explain themselves
+        if !added {
+            writeln!(&mut description, " - {}", entry.reason)?;
+        }
+
+        let causes: Vec<String> = serde_json::from_str(&entry.causes)?;
+        for cause in causes {
+            writeln!(&mut description, " - {}", cause)?;
+        }
+        if let Some(version) = &entry.definition_version {
+            writeln!(&mut description, " - Definition version `{}`", version)?;
+        }
+    }
+    if entries.is_empty() {
+        description = "No role changes recorded for this user".to_string();
+    }
+
+    let embed = CreateEmbed::new()
+        .author(serenity::CreateEmbedAuthor::new(&user.name).icon_url(user.avatar_url().unwrap_or(user.default_avatar_url())))
+        .description(description)
+        .footer(serenity::CreateEmbedFooter::new(format!("Page {}/{}", page, page_count.max(1))));
+
+    ctx.send(CreateReply::default()
+        .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users())
+        .embed(embed)).await?;
+
+    Ok(())
+}
+
 /// Set whether the bot's refreshes should perform a "dry run" or actually change roles
 #[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
 async fn dryrun(
@@ -544,6 +733,47 @@ async fn dryrun(
     Ok(())
 }
 
+/// Set the channels the bot posts badge role changes in
+#[poise::command(slash_command, required_permissions = "MANAGE_GUILD")]
+async fn notifications(
+    ctx: PoiseContext<'_>,
+    #[description = "Channel for a log of every badge role change"]
+    logging: Option<GuildChannel>,
+    #[description = "Channel for public congratulations on new badges"]
+    congratulations: Option<GuildChannel>,
+    #[description = "Stop logging badge role changes"]
+    clear_logging: Option<bool>,
+    #[description = "Stop posting congratulations on new badges"]
+    clear_congratulations: Option<bool>
+) -> Result<(), RoleManagerError> {
+    let response = if let Some(id) = ctx.guild_id() {
+        let mut config = ServerConfig::read(id.get()).await?
+            .unwrap_or_default();
+
+        // Channels which weren't given keep their current setting unless they're cleared
+        if let Some(channel) = logging {
+            config.logging_channel = Some(channel.id.get());
+        } else if clear_logging == Some(true) {
+            config.logging_channel = None;
+        }
+        if let Some(channel) = congratulations {
+            config.congratulations_channel = Some(channel.id.get());
+        } else if clear_congratulations == Some(true) {
+            config.congratulations_channel = None;
+        }
+        config.write(id.get()).await?;
+
+        "Updated this server's notification channels".to_string()
+    } else {
+        "Can only use command on servers!".to_string()
+    };
+
+    ctx.send(CreateReply::default()
+        .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users())
+        .content(response)).await?;
+
+    Ok(())
+}
 
 /// Provides a general analysis of a skill role file
 #[poise::command(slash_command)]
@@ -556,40 +786,19 @@ async fn analyze(
     ctx.defer().await?;
     println!("Finished deferring response");
 
-    // Download the definition file
-    let response = reqwest::get(definition_file.url.clone())
-        .await.map_err(|err| RoleManagerError::new_edit(format!("Failed to download provided role definition file: {}", err)))?
-        .text().await.map_err(|err| RoleManagerError::new_edit(format!("Failed to interpret provided role definition file download: {}", err)))?;
+    let response = download_definition(&definition_file).await?;
     let response_str = response.as_str();
 
     let definition: RoleDefinition = json5::from_str(response_str)
         .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
 
     // Request relevant (steam,srcom) accounts from database
-    let connections: Vec<verified_connections::Model> = verified_connections::Entity::find()
-        .filter(verified_connections::Column::Removed.eq(0))
-        .all(ctx.data().db.as_ref())
-        .await?;
-
-    let mut users: Vec<Member> = Vec::new();
-    let mut offset: Option<u64> = None;
-
-    loop {
-        let iteration = ctx.http().get_guild_members(GuildId::new(146404426746167296), Some(1_000), offset).await?;
-        if !iteration.is_empty() {
-            offset = Some(iteration.get(iteration.len() - 1).unwrap().user.id.get());
-        }
+    let connections: Vec<verified_connections::Model> = ctx.data().store.connections().await?;
 
-        let end = iteration.len() < 1000;
+    let users = fetch_all_members(ctx.http(), analysis_guild(ctx)?).await?;
 
-        users.extend(iteration);
-
-        if end {
-            break;
-        }
-    }
-
-    let report = analyzer::full_analysis(definition, connections, users, ctx.data().srcom_state.clone(), ctx.data().cm_state.clone()).await?;
+    let user_ids = users.iter().map(|user| user.user.id.get()).collect();
+    let report = analyzer::full_analysis(definition, connections, user_ids, ctx.data().srcom_state.clone(), ctx.data().cm_state.clone(), ctx.data().analysis_workers).await?;
 
     let mut embed = CreateEmbed::new()
         .description(format!("Analyzed **{} Users** ({} CM, {} SRC)", report.total_users, report.steam_users, report.srcom_users))
@@ -606,6 +815,54 @@ async fn analyze(
     Ok(())
 }
 
+/// Compares which members would gain or lose badges when switching between two skill role files
+#[poise::command(slash_command)]
+async fn compare(
+    ctx: PoiseContext<'_>,
+    #[description = "Json5 file with the current skill role definitions"]
+    old: Attachment,
+    #[description = "Json5 file with the skill role definitions to switch to"]
+    new: Attachment
+) -> Result<(), RoleManagerError> {
+    println!("Deferring response");
+    ctx.defer().await?;
+    println!("Finished deferring response");
+
+    // Download both definition files
+    let mut definitions = Vec::new();
+    for definition_file in [&old, &new] {
+        let response = download_definition(definition_file).await?;
+
+        let definition: RoleDefinition = json5::from_str(&response)
+            .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file {}: {}", definition_file.filename, err)))?;
+        definitions.push(definition);
+    }
+
+    // Request relevant (steam,srcom) accounts from database
+    let connections: Vec<verified_connections::Model> = ctx.data().store.connections().await?;
+
+    let users = fetch_all_members(ctx.http(), analysis_guild(ctx)?).await?;
+
+    let user_names: Vec<(u64, String)> = users.iter()
+        .map(|user| (user.user.id.get(), user.user.name.clone()))
+        .collect();
+    let comparison = compare_definitions(&definitions[0], &definitions[1], &user_names, &connections, ctx.data().srcom_state.clone(), ctx.data().cm_state.clone()).await?;
+
+    let mut embed = CreateEmbed::new()
+        .title("Definition Comparison")
+        .description(format!("Compared **{} Users** from `{}` to `{}`", users.len(), old.filename, new.filename));
+    for badge in comparison.badges.iter().take(25) {
+        embed = embed.field(&badge.badge, format!("**+{}** gain, **-{}** lose, **{}** keep", badge.gained.len(), badge.lost.len(), badge.kept.len()), false);
+    }
+
+    ctx.send(poise::CreateReply::default()
+        .embed(embed)
+        .attachment(serenity::CreateAttachment::bytes(comparison.to_csv()?, "comparison.csv"))
+    ).await?;
+
+    Ok(())
+}
+
 /// Generates a CSV file reporting which users satisfy which requirements of a badge
 #[poise::command(slash_command)]
 async fn generate_report(
@@ -619,125 +876,121 @@ async fn generate_report(
     ctx.defer().await?;
     println!("Finished deferring response");
 
-    let (response_str, definition_filename): (String, String) = match definition_file {
-        Some(definition_file) => {
-            // Download the definition file
-            let response = reqwest::get(definition_file.url.clone())
-                .await.map_err(|err| RoleManagerError::new_edit(format!("Failed to download provided role definition file: {}", err)))?
-                .text().await.map_err(|err| RoleManagerError::new_edit(format!("Failed to interpret provided role definition file download: {}", err)))?;
-            (response.as_str().to_string(), definition_file.filename)
-        }
-        None => {
-            if let Some(guild_id) = ctx.guild_id() {
-                // Use this server's definition file
-                let definition_path = format!("server_definitions/{}.json5", guild_id.get());
-
-                if tokio::fs::try_exists(&definition_path).await? {
-                    (tokio::fs::read_to_string(&definition_path).await?, format!("{}.json5", guild_id.get()))
-                } else {
-                    ctx.reply("This server doesn't have a definition file set for it! Try attatching one.").await?;
-                    return Ok(())
-                }
-            } else {
-                ctx.reply("This command can only be run in servers.").await?;
-                return Ok(())
-            }
-        }
+    let (definition, response_str, definition_filename) = match load_definition(ctx, definition_file).await? {
+        Some(loaded) => loaded,
+        None => return Ok(())
     };
 
-    let definition: RoleDefinition = json5::from_str(&response_str)
-        .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
-
     // Request relevant (steam,srcom) accounts from database
-    let connections: Vec<verified_connections::Model> = verified_connections::Entity::find()
-        .filter(verified_connections::Column::Removed.eq(0))
-        .all(ctx.data().db.as_ref())
-        .await?;
+    let connections: Vec<verified_connections::Model> = ctx.data().store.connections().await?;
 
-    let mut users: Vec<Member> = Vec::new();
-    let mut offset: Option<u64> = None;
+    let users = fetch_all_members(ctx.http(), analysis_guild(ctx)?).await?;
 
-    loop {
-        let iteration = ctx.http().get_guild_members(GuildId::new(146404426746167296), Some(1_000), offset).await?;
-        if !iteration.is_empty() {
-            offset = Some(iteration.get(iteration.len() - 1).unwrap().user.id.get());
+    let user_names: Vec<(u64, String)> = users.iter()
+        .map(|user| (user.user.id.get(), user.user.name.clone()))
+        .collect();
+    let report = match analyzer::badge_report(&definition, &badge_name, &user_names, &connections, ctx.data().srcom_state.clone(), ctx.data().cm_state.clone()).await? {
+        Some(report) => report,
+        None => {
+            ctx.reply(format!("This definition file does not contain a badge `{}`", badge_name)).await?;
+            return Ok(())
         }
+    };
 
-        let end = iteration.len() < 1000;
+    // Send response
+    let embed = CreateEmbed::new()
+        .description(format!("{}/{} Discord users meet the requirement for badge {}", report.users_meeting_badge, users.len(), badge_name))
+        .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_filename)));
 
-        users.extend(iteration);
+    ctx.send(poise::CreateReply::default()
+        .embed(embed)
+        .attachment(serenity::CreateAttachment::bytes(report.csv, format!("{}.csv", badge_name)))
+        .attachment(serenity::CreateAttachment::bytes(response_str.as_bytes(), definition_filename))
+    ).await?;
 
-        if end {
-            break;
-        }
-    }
+    Ok(())
+}
+
+/// Shows how many members would meet a requirement of a badge as one of its thresholds changes
+#[poise::command(slash_command)]
+#[allow(clippy::too_many_arguments)]
+async fn whatif(
+    ctx: PoiseContext<'_>,
+    #[description = "Badge containing the requirement"]
+    #[autocomplete = "autocomplete_badge"]
+    badge: String,
+    #[description = "Position of the requirement within the badge, starting from 1"]
+    #[min = 1]
+    requirement: u64,
+    #[description = "First threshold to check (points, rank or time)"]
+    from: String,
+    #[description = "Last threshold to check (points, rank or time)"]
+    to: String,
+    #[description = "Number of thresholds to check between the first and last (default 10)"]
+    #[min = 2]
+    #[max = 25]
+    steps: Option<u64>,
+    #[description = "Threshold to change, for requirements with more than one"]
+    parameter: Option<ThresholdParameter>,
+    #[description = "Json5 file describing skill role definitions"]
+    definition_file: Option<Attachment>
+) -> Result<(), RoleManagerError> {
+    println!("Deferring response");
+    ctx.defer().await?;
+    println!("Finished deferring response");
 
-    // Look up the badge definition and build a header for our sheet with it
-    let badge_definition = match definition.badges.iter().find(|badge| badge.name == badge_name) {
-        Some(bd) => bd,
+    let (definition, _, definition_filename) = match load_definition(ctx, definition_file).await? {
+        Some(loaded) => loaded,
+        None => return Ok(())
+    };
+
+    let badge_definition = match definition.badges.iter().find(|b| b.name == badge) {
+        Some(badge_definition) => badge_definition,
         None => {
-            ctx.reply(format!("This definition file does not contain a badge `{}`", badge_name)).await?;
+            ctx.reply(format!("This definition file does not contain a badge `{}`", badge)).await?;
+            return Ok(())
+        }
+    };
+    let requirement_definition = match badge_definition.requirements.get(requirement as usize - 1) {
+        Some(requirement_definition) => requirement_definition,
+        None => {
+            ctx.reply(format!("Badge `{}` only has {} requirements", badge, badge_definition.requirements.len())).await?;
             return Ok(())
         }
     };
-    let mut header = vec!["Discord User".to_string(), "Num Reqs Satisfied".to_string()];
-    for req in badge_definition.requirements.iter() {
-        header.push(req.format(ctx.data().srcom_state.clone()).await?);
-    }
-
-    // Write a CSV report
-    let mut report = csv::Writer::from_writer(vec![]);
-    report.write_record(&header).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
-
-    let mut users_meeting_requirement = 0;
-
-    for user in &users {
-        let analysis = user::analyze_user(
-            user.user.id.get(),
-            &definition,
-            &connections,
-            ctx.data().srcom_state.clone(),
-            ctx.data().cm_state.clone(),
-            false
-        ).await?;
-        let badge_analysis = match analysis.badges.iter().find(|analyzed_badge| analyzed_badge.definition == badge_definition) {
-            Some(badge_analysis) => badge_analysis,
-            None => continue
-        };
-
-        users_meeting_requirement += 1;
-
-        // Build report row
-        let mut row = vec![];
-        row.push(user.user.name.clone());
 
-        let mut met_requirements = 0;
-        for req in &badge_definition.requirements {
-            if badge_analysis.met_requirements.iter().any(|met_req| *met_req.definition == *req) {
-                row.push(format!("true"));
-                met_requirements += 1;
-            } else {
-                row.push(format!("false"));
+    let parameter = match parameter {
+        Some(parameter) => parameter,
+        None => match ThresholdParameter::default_for(requirement_definition) {
+            Some(parameter) => parameter,
+            None => {
+                ctx.reply(format!("Requirement `{}` has more than one threshold or none at all, choose one with `parameter`", requirement_definition.short_description())).await?;
+                return Ok(())
             }
         }
+    };
+    let sweep = ThresholdSweep::new(parameter, &from, &to, steps.unwrap_or(10))?;
+
+    // Request relevant (steam,srcom) accounts from database
+    let connections: Vec<verified_connections::Model> = ctx.data().store.connections().await?;
 
-        row.insert(1, format!("{}", met_requirements));
+    let users = fetch_all_members(ctx.http(), analysis_guild(ctx)?).await?;
 
-        report.write_record(&row).map_err(|e| RoleManagerError::new(format!("Failed to write to report: {}", e)))?;
+    let user_ids: Vec<u64> = users.iter().map(|user| user.user.id.get()).collect();
+    let results = sweep_requirement(requirement_definition, &sweep, &user_ids, &connections, ctx.data().srcom_state.clone(), ctx.data().cm_state.clone()).await?;
+
+    let mut description = format!("Currently: {}\n\n", requirement_definition.format(ctx.data().srcom_state.clone()).await?);
+    for step in &results {
+        writeln!(&mut description, "`{}` - **{}/{}** members", step.threshold, step.qualifying, users.len())?;
     }
 
-    // Send response
     let embed = CreateEmbed::new()
-        .description(format!("{}/{} Discord users meet the requirement for badge {}", users_meeting_requirement, users.len(), badge_name))
+        .title(format!("What if: {} requirement {}", badge, requirement))
+        .description(description)
         .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_filename)));
 
-    let report_inner = report.into_inner()
-        .map_err(|e| RoleManagerError::new(format!("Failed to generate report: {}", e)))?;
-
     ctx.send(poise::CreateReply::default()
         .embed(embed)
-        .attachment(serenity::CreateAttachment::bytes(report_inner, format!("{}.csv", badge_name)))
-        .attachment(serenity::CreateAttachment::bytes(response_str.as_bytes(), definition_filename))
     ).await?;
 
     Ok(())
@@ -756,45 +1009,17 @@ pub async fn user(
     ctx.defer().await?;
     println!("Finished deferring response");
 
-    let (response_str, definition_filename): (String, String) = match definition_file {
-        Some(definition_file) => {
-            // Download the definition file
-            let response = reqwest::get(definition_file.url.clone())
-                .await.map_err(|err| RoleManagerError::new_edit(format!("Failed to download provided role definition file: {}", err)))?
-                .text().await.map_err(|err| RoleManagerError::new_edit(format!("Failed to interpret provided role definition file download: {}", err)))?;
-            (response.as_str().to_string(), definition_file.filename)
-        }
-        None => {
-            if let Some(guild_id) = ctx.guild_id() {
-                // Use this server's definition file
-                let definition_path = format!("server_definitions/{}.json5", guild_id.get());
-
-                if tokio::fs::try_exists(&definition_path).await? {
-                    (tokio::fs::read_to_string(&definition_path).await?, format!("{}.json5", guild_id.get()))
-                } else {
-                    ctx.reply("This server doesn't have a definition file set for it! Try attatching one.").await?;
-                    return Ok(())
-                }
-            } else {
-                ctx.reply("This command can only be run in servers.").await?;
-                return Ok(())
-            }
-        }
+    let (definition, response_str, definition_filename) = match load_definition(ctx, definition_file).await? {
+        Some(loaded) => loaded,
+        None => return Ok(())
     };
 
-    let definition: RoleDefinition = json5::from_str(&response_str)
-        .map_err(|err| RoleManagerError::new_edit(format!("Invalid role definition file: {}", err)))?;
-
     let user = user.as_ref().unwrap_or(ctx.author());
 
     println!("Analyzing {}", user.name);
 
     // Request relevant (steam,srcom) accounts from database
-    let connections: Vec<verified_connections::Model> = verified_connections::Entity::find()
-        .filter(verified_connections::Column::UserId.eq(user.id.get() as i64))
-        .filter(verified_connections::Column::Removed.eq(0))
-        .all(ctx.data().db.as_ref())
-        .await?;
+    let connections: Vec<verified_connections::Model> = ctx.data().store.user_connections(user.id.get()).await?;
 
     let analysis = analyze_user(
         user.id.get(),
@@ -809,7 +1034,7 @@ pub async fn user(
     for badge in &analysis.badges {
         let mut requirement_descs = Vec::new();
         for met_requirement in &badge.met_requirements {
-            requirement_descs.push(format!("{}\n - {}", met_requirement.definition.format(ctx.data().srcom_state.clone()).await?, met_requirement.cause));
+            requirement_descs.push(met_requirement.describe(ctx.data().srcom_state.clone(), 0).await?);
         }
 
         fields.push((badge.definition.name.clone(), requirement_descs.join("\n")));
@@ -829,7 +1054,7 @@ pub async fn user(
         }
     }
 
-    let color = user.accent_colour.unwrap_or_else(|| Color::DARK_GREY);
+    let color = user.accent_colour.unwrap_or(Color::DARK_GREY);
 
     let mut embed = serenity::CreateEmbed::new()
         .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_filename)))
@@ -847,4 +1072,85 @@ pub async fn user(
     ).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Shows what a user is still missing for their nearest unearned badge
+#[poise::command(slash_command)]
+pub async fn progress(
+    ctx: PoiseContext<'_>,
+    #[description = "User to check progress for"]
+    user: Option<User>,
+    #[description = "Badge to check instead of the nearest one"]
+    #[autocomplete = "autocomplete_badge"]
+    badge: Option<String>,
+    #[description = "Json5 file describing skill role definitions"]
+    definition_file: Option<Attachment>
+) -> Result<(), RoleManagerError> {
+    ctx.defer().await?;
+
+    let (definition, _, definition_filename) = match load_definition(ctx, definition_file).await? {
+        Some(loaded) => loaded,
+        None => return Ok(())
+    };
+
+    let user = user.as_ref().unwrap_or(ctx.author());
+
+    let connections: Vec<verified_connections::Model> = ctx.data().store.user_connections(user.id.get()).await?;
+
+    let mut analysis = analyze_user(
+        user.id.get(),
+        &definition,
+        &connections,
+        ctx.data().srcom_state.clone(),
+        ctx.data().cm_state.clone(),
+        false
+    ).await?;
+    analysis.compute_gaps(&ctx.data().srcom_state, &ctx.data().cm_state).await?;
+
+    let target = match &badge {
+        Some(badge) => match analysis.badges.iter().chain(analysis.unearned_badges.iter()).find(|analyzed| analyzed.definition.name == *badge) {
+            Some(analyzed) => analyzed,
+            None => {
+                ctx.reply(format!("No badge named {} exists in {}.", badge, definition_filename)).await?;
+                return Ok(())
+            }
+        },
+        None => match analysis.nearest_incomplete_badge() {
+            Some(analyzed) => analyzed,
+            None => {
+                ctx.reply(format!("{} has already completed every badge!", user.name)).await?;
+                return Ok(())
+            }
+        }
+    };
+
+    let mut met_descs = Vec::new();
+    for met_requirement in &target.met_requirements {
+        met_descs.push(met_requirement.describe(ctx.data().srcom_state.clone(), 0).await?);
+    }
+
+    let mut missing_descs = Vec::new();
+    for unmet_requirement in &target.unmet_requirements {
+        missing_descs.push(unmet_requirement.describe(ctx.data().srcom_state.clone(), 0).await?);
+    }
+
+    let color = user.accent_colour.unwrap_or(Color::DARK_GREY);
+
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("{} - {}/{} requirements", target.definition.name, target.met_requirements.len(), target.definition.requirements.len()))
+        .footer(serenity::CreateEmbedFooter::new(format!("Context: {}", definition_filename)))
+        .color(color)
+        .author(serenity::CreateEmbedAuthor::new(&user.name).icon_url(user.avatar_url().unwrap_or(user.default_avatar_url())));
+    if !met_descs.is_empty() {
+        embed = embed.field("Met", met_descs.join("\n"), false);
+    }
+    if !missing_descs.is_empty() {
+        embed = embed.field("Missing", missing_descs.join("\n"), false);
+    }
+
+    ctx.send(poise::CreateReply::default()
+        .embed(embed)
+    ).await?;
+
+    Ok(())
+}
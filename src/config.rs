@@ -6,7 +6,28 @@ use serde::Deserialize;
 pub struct Config {
     pub discord_application_id: u64,
     pub discord_bot_token: String,
-    pub database_url: String
+    /// LumaDB instance holding connections and manual assignments. Either this or `local_store` is required.
+    #[serde(default)]
+    pub database_url: Option<String>,
+    /// JSON5 file seeding the in-memory store with connections and manual assignments
+    #[serde(default)]
+    pub local_store: Option<String>,
+    /// Directory where downloaded boards are kept, so restarts don't need to download them again
+    #[serde(default)]
+    pub board_cache: Option<String>,
+    /// How many members are analyzed at once
+    #[serde(default = "default_analysis_workers")]
+    pub analysis_workers: usize,
+    /// Guilds which slash commands are registered in
+    #[serde(default)]
+    pub command_guilds: Vec<u64>,
+    /// Guild whose members are used by `/analyze` and `/generate_report`. Those commands are unavailable without one.
+    #[serde(default)]
+    pub analysis_guild: Option<u64>
+}
+
+fn default_analysis_workers() -> usize {
+    crate::analyzer::pipeline::DEFAULT_ANALYSIS_WORKERS
 }
 
 pub fn load_config() -> Config {
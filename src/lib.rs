@@ -4,4 +4,6 @@ pub mod config;
 pub mod error;
 pub mod analyzer;
 pub mod model;
+pub mod roles;
 pub mod server;
+pub mod store;
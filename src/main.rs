@@ -5,9 +5,13 @@ use std::sync::Arc;
 use chrono::Duration;
 
 use sea_orm::{Database, DatabaseConnection};
+use role_manager::boards::cache::{BoardCache, FileBoardCache};
 use role_manager::boards::cm::CmBoardsState;
 use role_manager::boards::srcom::SrComBoardsState;
 use role_manager::error::RoleManagerError;
+use role_manager::store::Store;
+use role_manager::store::database::DatabaseStore;
+use role_manager::store::memory::MemoryStore;
 
 #[tokio::main]
 async fn main() -> Result<(), RoleManagerError> {
@@ -15,14 +19,31 @@ async fn main() -> Result<(), RoleManagerError> {
 
     tracing_subscriber::fmt().with_max_level(tracing::Level::DEBUG).with_test_writer().init();
 
-    let db: DatabaseConnection = Database::connect(&config.database_url).await.expect(
-        format!("Failed to open connection to database at {}", &config.database_url).as_str()
-    );
-
-    let srcom_state = SrComBoardsState::new(Duration::minutes(15));
-    let cm_state = CmBoardsState::new(Duration::minutes(15));
-
-    bot::create_bot(config, Arc::new(db), srcom_state, cm_state).await?;
+    let store: Arc<dyn Store> = match (&config.database_url, &config.local_store) {
+        (Some(database_url), _) => {
+            let db: DatabaseConnection = Database::connect(database_url).await.expect(
+                format!("Failed to open connection to database at {}", database_url).as_str()
+            );
+            let store = DatabaseStore::new(Arc::new(db));
+            if let Err(err) = store.create_tables().await {
+                eprintln!("Warning: Failed to create the role change log table, role changes won't be recorded: {}", err);
+            }
+            Arc::new(store)
+        }
+        (None, Some(local_store)) => Arc::new(MemoryStore::load(local_store).await?),
+        // Without any connections every badge role would be removed, so refuse to start instead
+        (None, None) => return Err(RoleManagerError::new("No database_url or local_store configured in config.toml".to_string()))
+    };
+
+    let mut srcom_state = SrComBoardsState::new(Duration::minutes(15));
+    let mut cm_state = CmBoardsState::new(Duration::minutes(15));
+    if let Some(board_cache) = &config.board_cache {
+        let cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(board_cache));
+        srcom_state = srcom_state.with_cache(Arc::clone(&cache));
+        cm_state = cm_state.with_cache(cache);
+    }
+
+    bot::create_bot(config, store, srcom_state, cm_state).await?;
 
     Ok(())
 }
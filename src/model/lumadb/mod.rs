@@ -16,6 +16,7 @@ pub mod ping_counts;
 pub mod ping_leaderboard;
 pub mod pinned_messages;
 pub mod render_results;
+pub mod role_change_log;
 pub mod servers;
 pub mod spam_keywords;
 pub mod tracked_streams;
@@ -14,6 +14,7 @@ pub use super::ping_counts::Entity as PingCounts;
 pub use super::ping_leaderboard::Entity as PingLeaderboard;
 pub use super::pinned_messages::Entity as PinnedMessages;
 pub use super::render_results::Entity as RenderResults;
+pub use super::role_change_log::Entity as RoleChangeLog;
 pub use super::servers::Entity as Servers;
 pub use super::spam_keywords::Entity as SpamKeywords;
 pub use super::tracked_streams::Entity as TrackedStreams;
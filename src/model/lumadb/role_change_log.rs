@@ -0,0 +1,33 @@
+//! SeaORM Entity for the role change log, created by `migrations/role_change_log.sql`
+
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "role_change_log")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub server_id: i64,
+    pub user_id: i64,
+    pub role_id: i64,
+    pub badge: String,
+    pub completed: i8,
+    pub direction: String,
+    #[sea_orm(column_type = "Text")]
+    pub reason: String,
+    #[sea_orm(column_type = "Text")]
+    pub causes: String,
+    pub definition_version: Option<String>,
+    pub changed_at: chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,77 @@
+use std::future::Future;
+use serenity::all::{GuildId, Http, RoleId, UserId};
+use crate::error::RoleManagerError;
+use crate::roles::{RoleChange, RoleChangeKind};
+use crate::store::ChangeLogStore;
+
+/// Applies planned role changes somewhere
+pub trait RoleExecutor {
+    fn apply(&self, guild_id: u64, change: &RoleChange) -> impl Future<Output = Result<(), RoleManagerError>> + Send;
+}
+
+/// Applies role changes to members through the Discord API
+pub struct DiscordExecutor<'a> {
+    pub http: &'a Http
+}
+
+impl RoleExecutor for DiscordExecutor<'_> {
+    async fn apply(&self, guild_id: u64, change: &RoleChange) -> Result<(), RoleManagerError> {
+        let guild_id = GuildId::new(guild_id);
+        let user_id = UserId::new(change.user_id);
+        let role_id = RoleId::new(change.role_id);
+
+        match change.kind {
+            RoleChangeKind::Add => self.http.add_member_role(guild_id, user_id, role_id, Some(&change.reason)).await?,
+            RoleChangeKind::Remove => self.http.remove_member_role(guild_id, user_id, role_id, None).await?
+        }
+
+        Ok(())
+    }
+}
+
+/// Leaves every role untouched, for servers with `dryrun` enabled and previews
+pub struct DryRunExecutor;
+
+impl RoleExecutor for DryRunExecutor {
+    async fn apply(&self, _guild_id: u64, _change: &RoleChange) -> Result<(), RoleManagerError> {
+        Ok(())
+    }
+}
+
+/// Records every change applied by another executor in a change log.
+/// Recording is best-effort, a failing change log never stops the role sync.
+pub struct AuditLogExecutor<'a, E: RoleExecutor> {
+    pub inner: E,
+    pub log: &'a dyn ChangeLogStore,
+    pub definition_version: Option<String>
+}
+
+impl<E: RoleExecutor + Sync> RoleExecutor for AuditLogExecutor<'_, E> {
+    async fn apply(&self, guild_id: u64, change: &RoleChange) -> Result<(), RoleManagerError> {
+        self.inner.apply(guild_id, change).await?;
+
+        if let Err(err) = self.log.record_change(guild_id, change, self.definition_version.clone()).await {
+            eprintln!("Failed to record role change for user {} in server {}: {}", change.user_id, guild_id, err);
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies every change in a plan in order, stopping at the first failure
+pub async fn execute_plan(guild_id: u64, plan: &[RoleChange], executor: &impl RoleExecutor) -> Result<(), RoleManagerError> {
+    for change in plan {
+        let role_kind = if change.completed { "*completed* role" } else { "role" };
+        match change.kind {
+            RoleChangeKind::Add => {
+                println!("Trying to add {} {} to user {}", role_kind, change.badge, change.user_name);
+                println!(" - {}", change.reason);
+            }
+            RoleChangeKind::Remove => println!("Trying to remove {} {} from user {}", role_kind, change.badge, change.user_name)
+        }
+
+        executor.apply(guild_id, change).await?;
+    }
+
+    Ok(())
+}
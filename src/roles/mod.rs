@@ -0,0 +1,43 @@
+use std::fmt::{Display, Formatter};
+
+pub mod executor;
+pub mod notifications;
+pub mod planner;
+
+/// A guild member along with the roles they currently have
+#[derive(Debug, Clone)]
+pub struct MemberRoles {
+    pub user_id: u64,
+    pub name: String,
+    pub roles: Vec<u64>
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RoleChangeKind {
+    Add,
+    Remove
+}
+
+impl Display for RoleChangeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => write!(f, "add"),
+            Self::Remove => write!(f, "remove")
+        }
+    }
+}
+
+/// A single role addition or removal planned for a member
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RoleChange {
+    pub user_id: u64,
+    pub user_name: String,
+    pub role_id: u64,
+    pub badge: String,
+    /// Whether this is the role for completing every requirement of the badge
+    pub completed: bool,
+    pub kind: RoleChangeKind,
+    pub reason: String,
+    /// Each met requirement which justified an addition, along with what met it
+    pub causes: Vec<String>
+}
@@ -0,0 +1,91 @@
+use serenity::all::{ChannelId, Color, CreateAllowedMentions, CreateEmbed, CreateMessage, Http};
+use crate::error::RoleManagerError;
+use crate::roles::{RoleChange, RoleChangeKind};
+use crate::server::ServerConfig;
+
+/// Discord limits embed descriptions to 4096 characters, so digests are split before reaching it
+const DIGEST_LENGTH: usize = 4000;
+
+/// Posts digests of applied role changes to the channels configured for a server
+pub async fn notify_changes(http: &Http, config: &ServerConfig, plan: &[RoleChange]) -> Result<(), RoleManagerError> {
+    if plan.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(channel_id) = config.logging_channel {
+        let lines = plan.iter().map(|change| {
+            let verb = match change.kind {
+                RoleChangeKind::Add => "gained",
+                RoleChangeKind::Remove => "lost"
+            };
+            let completed = if change.completed { " (completed)" } else { "" };
+
+            let mut line = format!("<@{}> {} **{}**{}", change.user_id, verb, change.badge, completed);
+            // Removals list what's missing as their causes, so their reason is still needed to say what was lost
+            if change.causes.is_empty() || change.kind == RoleChangeKind::Remove {
+                line.push_str(&format!("\n - {}", change.reason));
+            }
+            for cause in &change.causes {
+                line.push_str(&format!("\n - {}", cause));
+            }
+            line
+        });
+
+        for digest in digests(lines) {
+            ChannelId::new(channel_id).send_message(http, CreateMessage::new()
+                .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users())
+                .embed(CreateEmbed::new()
+                    .title("Badge Role Changes")
+                    .color(Color::BLURPLE)
+                    .description(digest))
+            ).await?;
+        }
+    }
+
+    if let Some(channel_id) = config.congratulations_channel {
+        let lines = plan.iter()
+            .filter(|change| change.kind == RoleChangeKind::Add)
+            .map(|change| if change.completed {
+                format!("<@{}> completed every requirement of **{}**!", change.user_id, change.badge)
+            } else {
+                format!("<@{}> earned **{}**!", change.user_id, change.badge)
+            });
+
+        for digest in digests(lines) {
+            ChannelId::new(channel_id).send_message(http, CreateMessage::new()
+                .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users())
+                .embed(CreateEmbed::new()
+                    .title("Congratulations!")
+                    .color(Color::GOLD)
+                    .description(digest))
+            ).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Joins lines into as few embed descriptions as possible
+fn digests(lines: impl Iterator<Item = String>) -> Vec<String> {
+    let mut digests = vec![];
+    let mut current = String::new();
+
+    for mut line in lines {
+        if line.len() > DIGEST_LENGTH {
+            line.truncate(line.floor_char_boundary(DIGEST_LENGTH));
+        }
+
+        if !current.is_empty() && current.len() + line.len() + 1 > DIGEST_LENGTH {
+            digests.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        digests.push(current);
+    }
+
+    digests
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use itertools::Itertools;
+use crate::analyzer::role_definition::{BadgeDefinition, RoleDefinition};
+use crate::analyzer::user::{AnalyzedUser, AnalyzedUserBadge, UnmetRequirement};
+use crate::model::lumadb::manual_role_assignments;
+use crate::roles::{MemberRoles, RoleChange, RoleChangeKind};
+use crate::server::ServerConfig;
+
+/// Works out which badge roles need to be added to or removed from each member, without talking to Discord.
+/// Members without an analysis are treated as meeting no requirements. Removals are explained by the unmet
+/// requirements of the analysis, along with their gaps if those were computed.
+pub fn plan_role_changes(
+    definition: &RoleDefinition,
+    config: &ServerConfig,
+    members: &[MemberRoles],
+    analyses: &[AnalyzedUser],
+    manual_assignments: &[manual_role_assignments::Model]
+) -> Vec<RoleChange> {
+    let valid_badges = config.valid_badges(definition);
+    let valid_completed_badges = config.valid_completed_badges(definition);
+
+    let analyses: HashMap<u64, &AnalyzedUser> = analyses.iter()
+        .map(|analysis| (analysis.discord_id, analysis))
+        .collect();
+
+    let mut changes = Vec::new();
+
+    for member in members {
+        let analysis = analyses.get(&member.user_id);
+        let analyzed_badges: &[AnalyzedUserBadge] = analysis
+            .map(|analysis| analysis.badges.as_slice())
+            .unwrap_or(&[]);
+        let unearned_badges: &[AnalyzedUserBadge] = analysis
+            .map(|analysis| analysis.unearned_badges.as_slice())
+            .unwrap_or(&[]);
+
+        for badge_definition in &definition.badges {
+            let analyzed_badge = analyzed_badges.iter()
+                .find(|analyzed_badge| analyzed_badge.definition == badge_definition);
+            let unmet_requirements: &[UnmetRequirement] = analyzed_badge
+                .or_else(|| unearned_badges.iter().find(|unearned_badge| unearned_badge.definition == badge_definition))
+                .map(|badge| badge.unmet_requirements.as_slice())
+                .unwrap_or(&[]);
+
+            // Make sure the user has roles that they are supposed to, and doesn't have ones they aren't
+            if let Some(role_id) = valid_badges.get(badge_definition) {
+                match analyzed_badge {
+                    Some(analyzed_badge) => {
+                        if !member.roles.contains(role_id) {
+                            changes.push(addition(member, *role_id, analyzed_badge, false));
+                        }
+                    }
+                    None => {
+                        if should_remove(member, *role_id, badge_definition, manual_assignments) {
+                            changes.push(removal(member, *role_id, badge_definition, unmet_requirements, false, "No longer meets any requirement"));
+                        }
+                    }
+                }
+            }
+
+            // Check if completed badge should also be awarded
+            if let Some(role_id) = valid_completed_badges.get(badge_definition) {
+                match analyzed_badge.filter(|analyzed_badge| analyzed_badge.is_complete()) {
+                    Some(analyzed_badge) => {
+                        if !member.roles.contains(role_id) {
+                            changes.push(addition(member, *role_id, analyzed_badge, true));
+                        }
+                    }
+                    None => {
+                        if should_remove(member, *role_id, badge_definition, manual_assignments) {
+                            changes.push(removal(member, *role_id, badge_definition, unmet_requirements, true, "No longer meets all requirements"));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+fn should_remove(member: &MemberRoles, role_id: u64, badge_definition: &BadgeDefinition, manual_assignments: &[manual_role_assignments::Model]) -> bool {
+    if !badge_definition.can_autoremove() || !member.roles.contains(&role_id) {
+        return false;
+    }
+
+    // Roles which were manually assigned are left alone
+    !manual_assignments.iter()
+        .any(|assignment| assignment.user_id as u64 == member.user_id && assignment.role_id as u64 == role_id)
+}
+
+fn addition(member: &MemberRoles, role_id: u64, analyzed_badge: &AnalyzedUserBadge, completed: bool) -> RoleChange {
+    RoleChange {
+        user_id: member.user_id,
+        user_name: member.name.clone(),
+        role_id,
+        badge: analyzed_badge.definition.name.clone(),
+        completed,
+        kind: RoleChangeKind::Add,
+        reason: analyzed_badge.met_requirements.iter()
+            .map(|r| r.definition.short_description())
+            .join(", "),
+        causes: analyzed_badge.met_requirements.iter()
+            .map(|r| format!("{}: {}", r.definition.short_description(), r.cause))
+            .collect()
+    }
+}
+
+fn removal(member: &MemberRoles, role_id: u64, badge_definition: &BadgeDefinition, unmet_requirements: &[UnmetRequirement], completed: bool, reason: &str) -> RoleChange {
+    RoleChange {
+        user_id: member.user_id,
+        user_name: member.name.clone(),
+        role_id,
+        badge: badge_definition.name.clone(),
+        completed,
+        kind: RoleChangeKind::Remove,
+        reason: reason.to_string(),
+        causes: unmet_requirements.iter()
+            .map(|r| match &r.gap {
+                Some(gap) => format!("{}: {}", r.definition.short_description(), gap),
+                None => format!("{}: Not met", r.definition.short_description())
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
 use crate::analyzer::role_definition::{BadgeDefinition, RoleDefinition};
 use crate::error::RoleManagerError;
 
@@ -8,7 +11,13 @@ pub struct ServerConfig {
     pub dry_run: bool,
     pub badge_roles: HashMap<String, u64>,
     #[serde(default)]
-    pub completed_badge_roles: HashMap<String, u64>
+    pub completed_badge_roles: HashMap<String, u64>,
+    /// Channel receiving a digest of every badge role change
+    #[serde(default)]
+    pub logging_channel: Option<u64>,
+    /// Channel receiving public congratulations for newly earned badges
+    #[serde(default)]
+    pub congratulations_channel: Option<u64>
 }
 
 impl ServerConfig {
@@ -24,6 +33,25 @@ impl ServerConfig {
         }
     }
 
+    /// Ids of every server which has a configuration file
+    pub async fn configured_servers() -> Result<Vec<u64>, RoleManagerError> {
+        tokio::fs::create_dir_all("server_configs").await?;
+
+        let mut servers = Vec::new();
+        let mut entries = tokio::fs::read_dir("server_configs").await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            if let Some(id) = file_name.to_str()
+                .and_then(|name| name.strip_suffix(".json5"))
+                .and_then(|id| id.parse().ok()) {
+                servers.push(id);
+            }
+        }
+        servers.sort();
+
+        Ok(servers)
+    }
+
     pub async fn write(&self, server_id: u64) -> Result<(), RoleManagerError> {
         tokio::fs::create_dir_all("server_configs").await?;
         let config_path = format!("server_configs/{}.json5", server_id);
@@ -52,4 +80,87 @@ impl ServerConfig {
                 }
             }))
     }
+
+    /// Badge names which have a role configured but no longer match any badge in the definition
+    pub fn unmatched_badge_roles(&self, definition: &RoleDefinition) -> Vec<String> {
+        let mut unmatched: Vec<String> = self.badge_roles.keys()
+            .chain(self.completed_badge_roles.keys())
+            .filter(|name| !definition.badges.iter().any(|badge| &badge.name == *name))
+            .cloned()
+            .collect();
+        unmatched.sort();
+        unmatched.dedup();
+
+        unmatched
+    }
+}
+
+pub async fn read_definition(server_id: u64) -> Result<Option<RoleDefinition>, RoleManagerError> {
+    let definition_path = format!("server_definitions/{}.json5", server_id);
+
+    if tokio::fs::try_exists(&definition_path).await? {
+        let definition_content = tokio::fs::read_to_string(&definition_path).await?;
+        Ok(Some(json5::from_str(&definition_content)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The version of the definition currently used by a server, which is a hash of its contents.
+/// Backups of previous definitions are named by when they were replaced and their version.
+pub async fn definition_version(server_id: u64) -> Result<Option<String>, RoleManagerError> {
+    let definition_path = format!("server_definitions/{}.json5", server_id);
+
+    if tokio::fs::try_exists(&definition_path).await? {
+        let definition_content = tokio::fs::read(&definition_path).await?;
+        Ok(Some(content_version(&definition_content)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Short hex digest identifying a definition by its contents
+pub fn content_version(contents: &[u8]) -> String {
+    Sha256::digest(contents).iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Replaces the definition used by a server, keeping the previous definition as a versioned backup.
+/// Returns the path of the backup, if there was a previous definition.
+pub async fn replace_definition(server_id: u64, contents: &[u8]) -> Result<Option<String>, RoleManagerError> {
+    tokio::fs::create_dir_all("server_definitions").await?;
+    let definition_path = format!("server_definitions/{}.json5", server_id);
+
+    let backup_path = match definition_version(server_id).await? {
+        Some(version) => {
+            let history_dir = format!("server_definitions/history/{}", server_id);
+            tokio::fs::create_dir_all(&history_dir).await?;
+
+            // The timestamp keeps backups of the same version apart, and create_new guards against ever
+            // overwriting an earlier backup
+            let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.3f");
+            let backup_path = format!("{}/{}-{}.json5", history_dir, timestamp, version);
+            let previous = tokio::fs::read(&definition_path).await?;
+            let mut backup = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&backup_path).await
+                .map_err(|err| RoleManagerError::new(format!("Failed to create definition backup {}: {}", backup_path, err)))?;
+            backup.write_all(&previous).await?;
+            // Tokio finishes writes in the background, so make sure the backup is on disk before replacing anything
+            backup.flush().await?;
+            backup.sync_all().await?;
+            Some(backup_path)
+        }
+        None => None
+    };
+
+    // Write to a temporary file first so the background loop never sees a partially written definition
+    let temp_path = format!("{}.tmp", definition_path);
+    tokio::fs::write(&temp_path, contents).await?;
+    tokio::fs::rename(&temp_path, &definition_path).await?;
+
+    Ok(backup_path)
 }
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, NotSet, PaginatorTrait, QueryFilter, QueryOrder, Set};
+use crate::error::RoleManagerError;
+use crate::model::lumadb::{manual_role_assignments, role_change_log, verified_connections};
+use crate::roles::RoleChange;
+use crate::store::{AssignmentStore, ChangeLogStore, ConnectionStore, StoreFuture};
+
+/// Store backed by the LumaDB tables
+#[derive(Debug, Clone)]
+pub struct DatabaseStore {
+    db: Arc<DatabaseConnection>
+}
+
+impl DatabaseStore {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        DatabaseStore {
+            db
+        }
+    }
+
+    /// Creates the tables owned by the role manager if they don't exist yet.
+    /// Every other table is managed by LumaDB itself.
+    pub async fn create_tables(&self) -> Result<(), RoleManagerError> {
+        self.db.execute_unprepared(include_str!("../../migrations/role_change_log.sql")).await?;
+
+        Ok(())
+    }
+}
+
+impl ConnectionStore for DatabaseStore {
+    fn connections(&self) -> StoreFuture<'_, Vec<verified_connections::Model>> {
+        Box::pin(async move {
+            Ok(verified_connections::Entity::find()
+                .filter(verified_connections::Column::Removed.eq(0))
+                .all(self.db.as_ref())
+                .await?)
+        })
+    }
+
+    fn user_connections(&self, user_id: u64) -> StoreFuture<'_, Vec<verified_connections::Model>> {
+        Box::pin(async move {
+            Ok(verified_connections::Entity::find()
+                .filter(verified_connections::Column::UserId.eq(user_id as i64))
+                .filter(verified_connections::Column::Removed.eq(0))
+                .all(self.db.as_ref())
+                .await?)
+        })
+    }
+}
+
+impl AssignmentStore for DatabaseStore {
+    fn manual_assignments(&self, server_id: u64) -> StoreFuture<'_, Vec<manual_role_assignments::Model>> {
+        Box::pin(async move {
+            Ok(manual_role_assignments::Entity::find()
+                .filter(manual_role_assignments::Column::ServerId.eq(server_id as i64))
+                .all(self.db.as_ref())
+                .await?)
+        })
+    }
+}
+
+impl ChangeLogStore for DatabaseStore {
+    fn record_change<'a>(&'a self, server_id: u64, change: &'a RoleChange, definition_version: Option<String>) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            role_change_log::ActiveModel {
+                id: NotSet,
+                server_id: Set(server_id as i64),
+                user_id: Set(change.user_id as i64),
+                role_id: Set(change.role_id as i64),
+                badge: Set(change.badge.clone()),
+                completed: Set(change.completed as i8),
+                direction: Set(change.kind.to_string()),
+                reason: Set(change.reason.clone()),
+                causes: Set(serde_json::to_string(&change.causes)?),
+                definition_version: Set(definition_version),
+                changed_at: Set(Utc::now())
+            }.insert(self.db.as_ref()).await?;
+
+            Ok(())
+        })
+    }
+
+    fn change_history_pages(&self, server_id: u64, user_id: u64, page_size: u64) -> StoreFuture<'_, u64> {
+        Box::pin(async move {
+            Ok(history_query(server_id, user_id)
+                .paginate(self.db.as_ref(), page_size)
+                .num_pages().await?)
+        })
+    }
+
+    fn change_history_page(&self, server_id: u64, user_id: u64, page_size: u64, page: u64) -> StoreFuture<'_, Vec<role_change_log::Model>> {
+        Box::pin(async move {
+            Ok(history_query(server_id, user_id)
+                .paginate(self.db.as_ref(), page_size)
+                .fetch_page(page).await?)
+        })
+    }
+}
+
+fn history_query(server_id: u64, user_id: u64) -> sea_orm::Select<role_change_log::Entity> {
+    role_change_log::Entity::find()
+        .filter(role_change_log::Column::ServerId.eq(server_id as i64))
+        .filter(role_change_log::Column::UserId.eq(user_id as i64))
+        .order_by_desc(role_change_log::Column::ChangedAt)
+}
@@ -0,0 +1,168 @@
+use std::sync::Mutex;
+use chrono::Utc;
+use serde::Deserialize;
+use crate::error::RoleManagerError;
+use crate::model::lumadb::{manual_role_assignments, role_change_log, verified_connections};
+use crate::roles::RoleChange;
+use crate::store::{AssignmentStore, ChangeLogStore, ConnectionStore, StoreFuture};
+
+/// Store which only lives as long as the process, for running the bot locally and for tests
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    connections: Mutex<Vec<verified_connections::Model>>,
+    manual_assignments: Mutex<Vec<manual_role_assignments::Model>>,
+    change_log: Mutex<Vec<role_change_log::Model>>
+}
+
+/// Initial contents of a [`MemoryStore`], read from a JSON5 file
+#[derive(Deserialize, Debug, Default)]
+pub struct MemoryStoreSeed {
+    #[serde(default)]
+    pub connections: Vec<SeedConnection>,
+    #[serde(default)]
+    pub manual_assignments: Vec<SeedAssignment>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeedConnection {
+    pub user_id: u64,
+    /// `steam` or `srcom`
+    #[serde(rename = "type")]
+    pub connection_type: String,
+    pub id: String
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeedAssignment {
+    pub user_id: u64,
+    pub server_id: u64,
+    pub role_id: u64
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_seed(seed: MemoryStoreSeed) -> Self {
+        let store = Self::new();
+        for connection in seed.connections {
+            store.add_connection(connection.user_id, &connection.connection_type, &connection.id);
+        }
+        for assignment in seed.manual_assignments {
+            store.add_manual_assignment(assignment.user_id, assignment.server_id, assignment.role_id);
+        }
+
+        store
+    }
+
+    pub async fn load(path: &str) -> Result<Self, RoleManagerError> {
+        let seed_content = tokio::fs::read_to_string(path).await?;
+
+        Ok(Self::from_seed(json5::from_str(&seed_content)?))
+    }
+
+    pub fn add_connection(&self, user_id: u64, connection_type: &str, id: &str) {
+        self.connections.lock().unwrap().push(verified_connections::Model {
+            user_id: user_id as i64,
+            server_id: 0,
+            id: id.to_string(),
+            connection_type: connection_type.to_string(),
+            connection_name: id.to_string(),
+            token: None,
+            removed: 0,
+            association_level: 0,
+            association_tree: None,
+            notify: 0
+        });
+    }
+
+    pub fn add_manual_assignment(&self, user_id: u64, server_id: u64, role_id: u64) {
+        self.manual_assignments.lock().unwrap().push(manual_role_assignments::Model {
+            user_id: user_id as i64,
+            server_id: server_id as i64,
+            role_id: role_id as i64
+        });
+    }
+
+    /// Changes recorded for a user, most recent first
+    fn user_history(&self, server_id: u64, user_id: u64) -> Vec<role_change_log::Model> {
+        self.change_log.lock().unwrap().iter()
+            .rev()
+            .filter(|entry| entry.server_id == server_id as i64 && entry.user_id == user_id as i64)
+            .cloned()
+            .collect()
+    }
+}
+
+impl ConnectionStore for MemoryStore {
+    fn connections(&self) -> StoreFuture<'_, Vec<verified_connections::Model>> {
+        let connections = self.connections.lock().unwrap().iter()
+            .filter(|connection| connection.removed == 0)
+            .cloned()
+            .collect();
+
+        Box::pin(async move { Ok(connections) })
+    }
+
+    fn user_connections(&self, user_id: u64) -> StoreFuture<'_, Vec<verified_connections::Model>> {
+        let connections = self.connections.lock().unwrap().iter()
+            .filter(|connection| connection.removed == 0 && connection.user_id == user_id as i64)
+            .cloned()
+            .collect();
+
+        Box::pin(async move { Ok(connections) })
+    }
+}
+
+impl AssignmentStore for MemoryStore {
+    fn manual_assignments(&self, server_id: u64) -> StoreFuture<'_, Vec<manual_role_assignments::Model>> {
+        let assignments = self.manual_assignments.lock().unwrap().iter()
+            .filter(|assignment| assignment.server_id == server_id as i64)
+            .cloned()
+            .collect();
+
+        Box::pin(async move { Ok(assignments) })
+    }
+}
+
+impl ChangeLogStore for MemoryStore {
+    fn record_change<'a>(&'a self, server_id: u64, change: &'a RoleChange, definition_version: Option<String>) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let causes = serde_json::to_string(&change.causes)?;
+
+            let mut change_log = self.change_log.lock().unwrap();
+            let id = change_log.len() as i64 + 1;
+            change_log.push(role_change_log::Model {
+                id,
+                server_id: server_id as i64,
+                user_id: change.user_id as i64,
+                role_id: change.role_id as i64,
+                badge: change.badge.clone(),
+                completed: change.completed as i8,
+                direction: change.kind.to_string(),
+                reason: change.reason.clone(),
+                causes,
+                definition_version,
+                changed_at: Utc::now()
+            });
+
+            Ok(())
+        })
+    }
+
+    fn change_history_pages(&self, server_id: u64, user_id: u64, page_size: u64) -> StoreFuture<'_, u64> {
+        let entries = self.user_history(server_id, user_id).len() as u64;
+
+        Box::pin(async move { Ok(entries.div_ceil(page_size)) })
+    }
+
+    fn change_history_page(&self, server_id: u64, user_id: u64, page_size: u64, page: u64) -> StoreFuture<'_, Vec<role_change_log::Model>> {
+        let entries = self.user_history(server_id, user_id).into_iter()
+            .skip((page * page_size) as usize)
+            .take(page_size as usize)
+            .collect();
+
+        Box::pin(async move { Ok(entries) })
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use crate::error::RoleManagerError;
+use crate::model::lumadb::{manual_role_assignments, role_change_log, verified_connections};
+use crate::roles::RoleChange;
+
+pub mod database;
+pub mod memory;
+
+/// Future returned by store methods, boxed so stores can be chosen at runtime
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, RoleManagerError>> + Send + 'a>>;
+
+/// Source of the steam and speedrun.com accounts linked to discord users
+pub trait ConnectionStore: Send + Sync {
+    /// Every connection which hasn't been removed
+    fn connections(&self) -> StoreFuture<'_, Vec<verified_connections::Model>>;
+
+    /// The connections of a single user which haven't been removed
+    fn user_connections(&self, user_id: u64) -> StoreFuture<'_, Vec<verified_connections::Model>>;
+}
+
+/// Source of roles which were assigned by hand and must not be removed automatically
+pub trait AssignmentStore: Send + Sync {
+    fn manual_assignments(&self, server_id: u64) -> StoreFuture<'_, Vec<manual_role_assignments::Model>>;
+}
+
+/// Record of every role change applied to a server
+pub trait ChangeLogStore: Send + Sync {
+    fn record_change<'a>(&'a self, server_id: u64, change: &'a RoleChange, definition_version: Option<String>) -> StoreFuture<'a, ()>;
+
+    /// Number of pages of changes recorded for a user, with `page_size` changes per page
+    fn change_history_pages(&self, server_id: u64, user_id: u64, page_size: u64) -> StoreFuture<'_, u64>;
+
+    /// A page of changes recorded for a user, most recent first. Pages start at 0.
+    fn change_history_page(&self, server_id: u64, user_id: u64, page_size: u64, page: u64) -> StoreFuture<'_, Vec<role_change_log::Model>>;
+}
+
+/// Everything the bot keeps outside of its own files
+pub trait Store: ConnectionStore + AssignmentStore + ChangeLogStore + Debug {}
+
+impl<T: ConnectionStore + AssignmentStore + ChangeLogStore + Debug> Store for T {}
@@ -0,0 +1,620 @@
+mod common;
+
+use std::collections::HashMap;
+use role_manager::analyzer::{badge_report, full_analysis};
+use role_manager::analyzer::role_definition::{PartnerRestriction, Platform, RoleDefinition};
+use role_manager::boards::srcom::category::CategoryId;
+use role_manager::boards::srcom::game::GameId;
+use role_manager::boards::srcom::user::UserId;
+use role_manager::analyzer::compare::compare_definitions;
+use role_manager::analyzer::pipeline::analyze_users;
+use role_manager::analyzer::user::{analyze_user, AnalyzedUser, ExternalAccount, MetRequirementCause, RequirementGap};
+use role_manager::analyzer::whatif::{sweep_requirement, ThresholdParameter, ThresholdSweep};
+use common::{connections, FixtureServer, ALICE, BOB, CAROL};
+
+/// Wraps a single requirement in a definition with one badge named `Badge`
+fn single_requirement(requirement: &str) -> RoleDefinition {
+    json5::from_str(&format!(r#"{{ "badges": [ {{ "name": "Badge", "requirements": [ {} ] }} ] }}"#, requirement)).unwrap()
+}
+
+/// The users of [`connections`] who meet at least one requirement of each badge in the definition
+async fn badge_holders(server: &FixtureServer, definition: &RoleDefinition) -> HashMap<String, Vec<u64>> {
+    let mut holders: HashMap<String, Vec<u64>> = HashMap::new();
+
+    for discord_id in [ALICE, BOB, CAROL] {
+        let analysis = analyze_user(discord_id, definition, &connections().await, server.srcom_state(), server.cm_state(), false)
+            .await.unwrap();
+
+        for badge in &analysis.badges {
+            holders.entry(badge.definition.name.clone()).or_default().push(discord_id);
+        }
+    }
+
+    holders
+}
+
+async fn holders_of(requirement: &str) -> Vec<u64> {
+    let server = FixtureServer::start().await;
+    badge_holders(&server, &single_requirement(requirement)).await
+        .remove("Badge")
+        .unwrap_or_default()
+}
+
+const SP_NO_SLA: &str = r#""game": "om1mw4d2", "category": "jzd33ndn", "variables": [ { "variable": "9l7x7xzn", "choice": "z196dyy1" } ]"#;
+
+#[tokio::test]
+async fn rank_requirement() {
+    let top_one = holders_of(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, "top": 1 }}"#, SP_NO_SLA)).await;
+    assert_eq!(top_one, vec![ALICE]);
+
+    let top_three = holders_of(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, "top": 3 }}"#, SP_NO_SLA)).await;
+    assert_eq!(top_three, vec![ALICE, BOB, CAROL]);
+}
+
+#[tokio::test]
+async fn time_requirement() {
+    let holders = holders_of(&format!(r#"{{ "type": "time", "platform": "srcom", {}, "time": "01:01:00" }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, vec![ALICE]);
+}
+
+#[tokio::test]
+async fn board_filters() {
+    let pc = r#""filters": { "platform": "8gej2n93", "emulators": false }"#;
+
+    // Alice's run doesn't show up once the board is narrowed down
+    let holders = holders_of(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, {}, "top": 1 }}"#, SP_NO_SLA, pc)).await;
+    assert_eq!(holders, vec![BOB]);
+
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{
+        "type": "any",
+        "of": [
+            {{ "type": "rank", "platform": "srcom", {sp}, {pc}, "top": 1 }},
+            {{ "type": "rank", "platform": "srcom", {sp}, "top": 1 }}
+        ]
+    }}"#, sp = SP_NO_SLA, pc = pc));
+    let srcom_state = server.srcom_state();
+    for discord_id in [ALICE, BOB] {
+        analyze_user(discord_id, &definition, &connections().await, srcom_state.clone(), server.cm_state(), false).await.unwrap();
+    }
+
+    // Filtered and unfiltered boards are cached separately
+    let leaderboard_requests = server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/leaderboards/"))
+        .count();
+    assert_eq!(leaderboard_requests, 2);
+
+    let report = definition.validate(&srcom_state).await;
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(
+        report.badges[0].requirements[0].nested[0].preview,
+        "SRC - Portal 2 - Single Player (SP Category=No SLA) - Top 1 [platform 8gej2n93, no emulators]"
+    );
+
+    let invalid = single_requirement(&format!(
+        r#"{{ "type": "rank", "platform": "srcom", {}, "filters": {{ "region": "pr1mvn30", "date": "last week" }}, "top": 1 }}"#,
+        SP_NO_SLA
+    ));
+    let report = invalid.validate(&srcom_state).await;
+    assert_eq!(report.badges[0].requirements[0].problems, vec![
+        "Invalid date `last week`, expected YYYY-MM-DD",
+        "Region `pr1mvn30` is not a region of game `om1mw4d2` (Portal 2)"
+    ]);
+}
+
+#[tokio::test]
+async fn timing_method() {
+    // Alice and Bob's real times include loads, and Carol's run has no real time at all
+    let holders = holders_of(&format!(r#"{{ "type": "time", "platform": "srcom", {}, "time": "01:01:00", "timing": "realtime" }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, Vec::<u64>::new());
+
+    let holders = holders_of(&format!(r#"{{ "type": "time", "platform": "srcom", {}, "time": "01:03:00", "timing": "realtime" }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, vec![ALICE]);
+
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "ranktime", "platform": "srcom", {}, "time": "01:03:00", "timing": "realtime", "top": 3 }}"#, SP_NO_SLA));
+
+    let alice = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    assert!(alice.badges[0].met_requirements[0].cause.to_string().starts_with("[#1 - 1:02:00.000]"));
+
+    let mut carol = analyze_user(CAROL, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    carol.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert_eq!(carol.unearned_badges[0].unmet_requirements[0].gap.as_ref().unwrap().to_string(), "Best run has no RTA time");
+
+    let real_time = single_requirement(&format!(r#"{{ "type": "time", "platform": "srcom", {}, "time": "01:03:00", "timing": "realtime" }}"#, SP_NO_SLA));
+    let report = real_time.validate(&server.srcom_state()).await;
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(report.badges[0].requirements[0].preview, "SRC - Portal 2 - Single Player (No SLA) - Sub 01:03:00 RTA");
+
+    let in_game = single_requirement(&format!(r#"{{ "type": "time", "platform": "srcom", {}, "time": "01:03:00", "timing": "ingame" }}"#, SP_NO_SLA));
+    let report = in_game.validate(&server.srcom_state()).await;
+    assert_eq!(report.badges[0].requirements[0].problems, vec!["Game `om1mw4d2` (Portal 2) doesn't time runs in IGT, only in RTA, LRT"]);
+
+    let conflicting = single_requirement(&format!(
+        r#"{{ "type": "time", "platform": "srcom", {}, "filters": {{ "timing": "realtime_noloads" }}, "time": "01:03:00", "timing": "realtime" }}"#,
+        SP_NO_SLA
+    ));
+    let report = conflicting.validate(&server.srcom_state()).await;
+    assert_eq!(report.badges[0].requirements[0].problems, vec!["Board is ranked by LRT but the time is compared in RTA, set only one of them"]);
+}
+
+#[tokio::test]
+async fn filter_timing_method() {
+    // Without a timing of its own, times are compared in the method the filters rank the board by
+    let by_real_time = r#""filters": { "timing": "realtime" }"#;
+    let holders = holders_of(&format!(r#"{{ "type": "time", "platform": "srcom", {}, {}, "time": "01:01:00" }}"#, SP_NO_SLA, by_real_time)).await;
+    assert_eq!(holders, Vec::<u64>::new());
+
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "ranktime", "platform": "srcom", {}, {}, "time": "01:03:00", "top": 3 }}"#, SP_NO_SLA, by_real_time));
+
+    let alice = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    assert!(alice.badges[0].met_requirements[0].cause.to_string().starts_with("[#1 - 1:02:00.000]"));
+
+    let mut carol = analyze_user(CAROL, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    carol.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert_eq!(carol.unearned_badges[0].unmet_requirements[0].gap.as_ref().unwrap().to_string(), "Best run has no RTA time");
+}
+
+#[tokio::test]
+async fn rank_time_requirement() {
+    // Carol is within 1:05:00 but only ranks 3rd, and her faster run was rejected
+    let holders = holders_of(&format!(r#"{{ "type": "ranktime", "platform": "srcom", {}, "time": "01:05:00", "top": 2 }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, vec![ALICE, BOB]);
+}
+
+#[tokio::test]
+async fn partner_restriction() {
+    let coop = r#""game": "om1mw4d2", "category": "l9kv40kg""#;
+
+    let unrestricted = holders_of(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, "top": 2 }}"#, coop)).await;
+    assert_eq!(unrestricted, vec![ALICE, BOB, CAROL]);
+
+    // Alice's only run is with Carol, who has a better run with Bob
+    let restricted = holders_of(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, "partner": "rank>=", "top": 2 }}"#, coop)).await;
+    assert_eq!(restricted, vec![BOB, CAROL]);
+}
+
+#[tokio::test]
+async fn leaderboard_index() {
+    let server = FixtureServer::start().await;
+    let coop = server.srcom_state().fetch_leaderboard(GameId("om1mw4d2".to_string()), CategoryId("l9kv40kg".to_string()))
+        .await.unwrap();
+
+    let place = |user: &str, partner| coop.get_highest_run(UserId::try_from(user).unwrap(), partner).map(|place| place.place);
+    assert_eq!(place("user0001", None), Some(2));
+    assert_eq!(place("user0003", None), Some(1));
+    assert_eq!(place("user0001", Some(PartnerRestriction::RankGte)), None);
+    assert_eq!(place("user0003", Some(PartnerRestriction::RankGte)), Some(1));
+    assert_eq!(place("user0009", None), None);
+}
+
+#[tokio::test]
+async fn level_requirement() {
+    let holders = holders_of(r#"{ "type": "rank", "platform": "srcom", "game": "om1mw4d2", "category": "ilcat001", "level": "lvl00001", "top": 1 }"#).await;
+    assert_eq!(holders, vec![ALICE]);
+}
+
+#[tokio::test]
+async fn srcom_recent_requirement() {
+    // Alice's recent run is obsolete, and Carol's recent run is in another variable
+    let holders = holders_of(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, "months": 6 }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, vec![ALICE, BOB]);
+
+    let holders = holders_of(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, "months": 1200 }}"#, SP_NO_SLA)).await;
+    assert_eq!(holders, vec![ALICE, BOB, CAROL]);
+
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, "months": 6 }}"#, SP_NO_SLA));
+
+    let alice = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    let recent_date = (chrono::Utc::now() - chrono::Duration::days(10)).date_naive();
+    assert_eq!(
+        alice.badges[0].met_requirements[0].cause.to_string(),
+        format!("[1:00:50.000 on {}](https://www.speedrun.com/portal2/run/run00006)", recent_date)
+    );
+
+    let mut carol = analyze_user(CAROL, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    carol.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert_eq!(carol.unearned_badges[0].unmet_requirements[0].gap, Some(RequirementGap::LastRun { date: "2021-05-05".to_string() }));
+}
+
+#[tokio::test]
+async fn srcom_recent_requirement_with_filters() {
+    // Alice's runs don't show up on the narrowed down board
+    let pc = r#""filters": { "platform": "8gej2n93", "emulators": false }"#;
+    let holders = holders_of(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, {}, "months": 6 }}"#, SP_NO_SLA, pc)).await;
+    assert_eq!(holders, vec![BOB]);
+
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, {}, "months": 6 }}"#, SP_NO_SLA, pc));
+    let report = definition.validate(&server.srcom_state()).await;
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(
+        report.badges[0].requirements[0].preview,
+        "SRC - Portal 2 - Single Player (No SLA) - Activity in last 6 months [platform 8gej2n93, no emulators]"
+    );
+}
+
+#[tokio::test]
+async fn recent_leaderboard_runs_skip_the_runs_endpoint() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "recent", "platform": "srcom", {}, "months": 6 }}"#, SP_NO_SLA));
+
+    // Bob's leaderboard run is recent enough, while Alice's is only found among her obsolete runs
+    let bob = analyze_user(BOB, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    assert_eq!(bob.badges.len(), 1);
+    assert!(!server.requests().iter().any(|target| target.starts_with("/srcom/runs")));
+
+    let alice = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    assert_eq!(alice.badges.len(), 1);
+    assert!(server.requests().iter().any(|target| target.starts_with("/srcom/runs") && target.ends_with("user=user0001")));
+}
+
+#[tokio::test]
+async fn cm_recent_requirement() {
+    let holders = holders_of(r#"{ "type": "recent", "platform": "cm", "months": 6 }"#).await;
+    assert_eq!(holders, vec![BOB]);
+}
+
+#[tokio::test]
+async fn points_requirement() {
+    let sp = holders_of(r#"{ "type": "points", "leaderboard": "aggregated/sp", "points": 10000 }"#).await;
+    assert_eq!(sp, vec![ALICE]);
+
+    let overall = holders_of(r#"{ "type": "points", "leaderboard": "aggregated/overall", "points": 15000 }"#).await;
+    assert_eq!(overall, vec![ALICE, BOB]);
+}
+
+#[tokio::test]
+async fn cm_chamber_requirement() {
+    let top = holders_of(r#"{ "type": "cm_chamber", "chamber": 47458, "top": 3 }"#).await;
+    assert_eq!(top, vec![ALICE]);
+
+    let time = holders_of(r#"{ "type": "cm_chamber", "chamber": 47458, "time": "00:00:12.5" }"#).await;
+    assert_eq!(time, vec![ALICE, BOB]);
+
+    let any_run = holders_of(r#"{ "type": "cm_chamber", "chamber": 47458 }"#).await;
+    assert_eq!(any_run, vec![ALICE, BOB]);
+}
+
+#[tokio::test]
+async fn manual_requirement_is_never_met_by_analysis() {
+    let holders = holders_of(r#"{ "type": "manual" }"#).await;
+    assert!(holders.is_empty());
+}
+
+#[tokio::test]
+async fn requirement_groups() {
+    let points = r#"{ "type": "points", "leaderboard": "aggregated/sp", "points": 10000 }"#;
+    let active = r#"{ "type": "recent", "platform": "cm", "months": 6 }"#;
+    let coop = r#"{ "type": "rank", "platform": "srcom", "game": "om1mw4d2", "category": "l9kv40kg", "top": 1 }"#;
+
+    let all = holders_of(&format!(r#"{{ "type": "all", "of": [ {}, {} ] }}"#, points, active)).await;
+    assert!(all.is_empty());
+
+    let any = holders_of(&format!(r#"{{ "type": "any", "of": [ {}, {} ] }}"#, points, active)).await;
+    assert_eq!(any, vec![ALICE, BOB]);
+
+    let at_least = holders_of(&format!(r#"{{ "type": "at_least", "n": 2, "of": [ {}, {}, {} ] }}"#, points, active, coop)).await;
+    assert_eq!(at_least, vec![BOB]);
+
+    let server = FixtureServer::start().await;
+    let problems = |n: u64| {
+        let definition = single_requirement(&format!(r#"{{ "type": "at_least", "n": {}, "of": [ {}, {} ] }}"#, n, points, active));
+        let srcom_state = server.srcom_state();
+        async move { definition.validate(&srcom_state).await.badges[0].requirements[0].problems.clone() }
+    };
+    assert_eq!(problems(0).await, vec!["`n` must be at least 1, otherwise the group is always met"]);
+    assert_eq!(problems(2).await, Vec::<String>::new());
+    assert_eq!(problems(3).await, vec!["`n` is 3 but the group only has 2 requirements, so it can never be met"]);
+
+    // Groups are described by their type, even when the counts would fit another one
+    let preview = |group: String| {
+        let definition = single_requirement(&group);
+        let srcom_state = server.srcom_state();
+        async move { definition.validate(&srcom_state).await.badges[0].requirements[0].preview.clone() }
+    };
+    assert_eq!(preview(format!(r#"{{ "type": "any", "of": [ {} ] }}"#, points)).await, "Any of: (CM - SP - 10000 Points)");
+    assert_eq!(preview(format!(r#"{{ "type": "at_least", "n": 2, "of": [ {}, {} ] }}"#, points, active)).await, "At least 2 of: (CM - SP - 10000 Points; CM - Activity in last 6 months)");
+    assert_eq!(preview(format!(r#"{{ "type": "all", "of": [ {} ] }}"#, points)).await, "All of: (CM - SP - 10000 Points)");
+}
+
+#[tokio::test]
+async fn met_requirement_causes() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(
+        r#"{{ "type": "any", "of": [ {{ "type": "rank", "platform": "srcom", {}, "top": 1 }}, {{ "type": "cm_chamber", "chamber": 47458, "name": "Portal Gun", "chapter": "Chapter 1", "top": 1 }} ] }}"#,
+        SP_NO_SLA
+    ));
+
+    let analysis = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), true)
+        .await.unwrap();
+
+    let met = &analysis.badges[0].met_requirements[0];
+    assert!(matches!(met.cause, MetRequirementCause::Group { met: 2, needed: 1, total: 2 }));
+    assert_eq!(met.branches[0].cause.to_string(), "[#1 - 1:00:00.500](https://www.speedrun.com/portal2/run/run00001)");
+    assert_eq!(met.branches[1].cause.to_string(), "Chapter 1/Portal Gun - #1 - 10.25 (2026-01-01 12:00:00)");
+
+    let usernames: Vec<&str> = analysis.external_accounts.iter()
+        .map(|account| match account {
+            ExternalAccount::Cm { username, .. } | ExternalAccount::Srcom { username, .. } => username.as_str()
+        })
+        .collect();
+    assert_eq!(usernames, vec!["AliceCM", "Alice"]);
+}
+
+#[tokio::test]
+async fn unmet_requirement_gaps() {
+    let server = FixtureServer::start().await;
+    let definition: RoleDefinition = json5::from_str(&format!(r#"{{ "badges": [
+        {{ "name": "Runner", "requirements": [
+            {{ "type": "rank", "platform": "srcom", {sp}, "top": 1 }},
+            {{ "type": "points", "leaderboard": "aggregated/sp", "points": 10000 }},
+            {{ "type": "time", "platform": "srcom", {sp}, "time": "01:00:00" }}
+        ] }},
+        {{ "name": "Chamber", "requirements": [ {{ "type": "cm_chamber", "chamber": 47458, "top": 3 }} ] }}
+    ] }}"#, sp = SP_NO_SLA)).unwrap();
+
+    let gaps = |analysis: &AnalyzedUser| -> Vec<String> {
+        analysis.badges.iter().chain(analysis.unearned_badges.iter())
+            .flat_map(|badge| badge.unmet_requirements.iter().map(|unmet| unmet.gap.as_ref().unwrap().to_string()))
+            .collect()
+    };
+
+    let mut alice = analyze_user(ALICE, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    alice.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert_eq!(gaps(&alice), vec!["1:00:00.500 vs 01:00:00"]);
+    assert_eq!(alice.nearest_incomplete_badge().unwrap().definition.name, "Runner");
+
+    let mut bob = analyze_user(BOB, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    assert!(bob.unearned_badges[0].unmet_requirements.iter().all(|unmet| unmet.gap.is_none()));
+    bob.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert!(bob.badges.is_empty());
+    assert_eq!(gaps(&bob), vec!["Rank 2, need top 1", "9,000 / 10,000 points", "1:01:40.000 vs 01:00:00", "Rank 5, need top 3"]);
+
+    let mut carol = analyze_user(CAROL, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    carol.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+    assert_eq!(carol.unearned_badges[0].unmet_requirements[1].gap, Some(RequirementGap::NoAccount { platform: Platform::Cm }));
+    assert_eq!(carol.unearned_badges[1].unmet_requirements[0].gap.as_ref().unwrap().to_string(), "No linked steam account");
+}
+
+#[tokio::test]
+async fn nearest_badge_accounts_for_gap_size() {
+    let server = FixtureServer::start().await;
+    let definition: RoleDefinition = json5::from_str(r#"{ "badges": [
+        { "name": "Far", "requirements": [ { "type": "points", "leaderboard": "aggregated/sp", "points": 100000 } ] },
+        { "name": "Near", "requirements": [ { "type": "points", "leaderboard": "aggregated/sp", "points": 10000 } ] }
+    ] }"#).unwrap();
+
+    let mut bob = analyze_user(BOB, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    bob.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+
+    assert_eq!(bob.nearest_incomplete_badge().unwrap().definition.name, "Near");
+}
+
+#[tokio::test]
+async fn group_gaps_list_unmet_branches() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(
+        r#"{{ "type": "any", "of": [ {{ "type": "rank", "platform": "srcom", {}, "top": 1 }}, {{ "type": "points", "leaderboard": "aggregated/sp", "points": 10000 }} ] }}"#,
+        SP_NO_SLA
+    ));
+
+    let mut carol = analyze_user(CAROL, &definition, &connections().await, server.srcom_state(), server.cm_state(), false).await.unwrap();
+    carol.compute_gaps(&server.srcom_state(), &server.cm_state()).await.unwrap();
+
+    let group = &carol.unearned_badges[0].unmet_requirements[0];
+    assert_eq!(group.gap, Some(RequirementGap::Group { met: 0, needed: 1, total: 2 }));
+    let branches: Vec<String> = group.branches.iter().map(|branch| branch.gap.as_ref().unwrap().to_string()).collect();
+    assert_eq!(branches, vec!["Rank 3, need top 1", "No linked steam account"]);
+}
+
+#[tokio::test]
+async fn boards_are_cached() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{ "type": "rank", "platform": "srcom", {}, "top": 3 }}"#, SP_NO_SLA));
+
+    badge_holders(&server, &definition).await;
+
+    let leaderboard_requests = server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/leaderboards/"))
+        .count();
+    // Every analysis above used its own state, so only requests within one analysis can share the cache
+    assert_eq!(leaderboard_requests, 3);
+
+    let srcom_state = server.srcom_state();
+    for discord_id in [ALICE, BOB, CAROL] {
+        analyze_user(discord_id, &definition, &connections().await, srcom_state.clone(), server.cm_state(), false)
+            .await.unwrap();
+    }
+
+    let leaderboard_requests = server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/leaderboards/"))
+        .count();
+    assert_eq!(leaderboard_requests, 4);
+}
+
+#[tokio::test]
+async fn full_analysis_report() {
+    let server = FixtureServer::start().await;
+    let definition: RoleDefinition = json5::from_str(&format!(r#"{{
+        "badges": [
+            {{
+                "name": "Runner",
+                "requirements": [
+                    {{ "type": "rank", "platform": "srcom", {}, "top": 2 }},
+                    {{ "type": "recent", "platform": "cm", "months": 6 }}
+                ]
+            }},
+            {{
+                "name": "Veteran",
+                "requirements": [
+                    {{ "type": "manual" }},
+                    {{ "type": "points", "leaderboard": "aggregated/overall", "points": 20000 }}
+                ]
+            }}
+        ]
+    }}"#, SP_NO_SLA)).unwrap();
+
+    let report = full_analysis(definition, connections().await, vec![ALICE, BOB, CAROL], server.srcom_state(), server.cm_state(), 2)
+        .await.unwrap();
+
+    assert_eq!(report.total_users, 3);
+    assert_eq!(report.steam_users, 2);
+    assert_eq!(report.srcom_users, 3);
+
+    let summary = report.badge_summary(server.srcom_state()).await.unwrap();
+    assert_eq!(summary, vec![
+        (
+            "Runner - 2".to_string(),
+            "SRC - Portal 2 - Single Player (SP Category=No SLA) - Top 2 - **2/2**\nCM - Activity in last 6 months - **1/2**".to_string()
+        ),
+        (
+            "Veteran - 1".to_string(),
+            "Manual - **0/1**\nCM - Overall - 20000 Points - **1/1**".to_string()
+        )
+    ]);
+}
+
+#[tokio::test]
+async fn parallel_analysis_downloads_each_board_once() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(r#"{{
+        "type": "any",
+        "of": [
+            {{ "type": "rank", "platform": "srcom", {}, "top": 1 }},
+            {{ "type": "time", "platform": "srcom", {}, "time": "01:01:00" }}
+        ]
+    }}"#, SP_NO_SLA, SP_NO_SLA));
+
+    let analyses = analyze_users(&definition, &connections().await, &[CAROL, BOB, ALICE], &server.srcom_state(), &server.cm_state(), 3)
+        .await.unwrap();
+
+    let analyzed: Vec<u64> = analyses.iter().map(|analysis| analysis.discord_id).collect();
+    assert_eq!(analyzed, vec![CAROL, BOB, ALICE]);
+    assert_eq!(analyses[2].badges.len(), 1);
+
+    let leaderboard_requests = server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/leaderboards/"))
+        .count();
+    assert_eq!(leaderboard_requests, 1);
+}
+
+#[tokio::test]
+async fn badge_report_sheet() {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(&format!(
+        r#"{{ "type": "rank", "platform": "srcom", {}, "top": 1 }}, {{ "type": "recent", "platform": "cm", "months": 6 }}"#,
+        SP_NO_SLA
+    ));
+    let users = vec![(ALICE, "alice".to_string()), (BOB, "bob".to_string()), (CAROL, "carol".to_string())];
+
+    let report = badge_report(&definition, "Badge", &users, &connections().await, server.srcom_state(), server.cm_state())
+        .await.unwrap().unwrap();
+
+    assert_eq!(report.users_meeting_badge, 2);
+    assert_eq!(String::from_utf8(report.csv).unwrap(), "\
+Discord User,Num Reqs Satisfied,SRC - Portal 2 - Single Player (SP Category=No SLA) - Top 1,CM - Activity in last 6 months
+alice,1,true,false
+bob,1,false,true
+");
+
+    let missing = badge_report(&definition, "Missing", &users, &connections().await, server.srcom_state(), server.cm_state())
+        .await.unwrap();
+    assert!(missing.is_none());
+}
+
+async fn sweep(requirement: &str, parameter: ThresholdParameter, from: &str, to: &str, steps: u64) -> Vec<(String, u64)> {
+    let server = FixtureServer::start().await;
+    let definition = single_requirement(requirement);
+    let sweep = ThresholdSweep::new(parameter, from, to, steps).unwrap();
+
+    sweep_requirement(&definition.badges[0].requirements[0], &sweep, &[ALICE, BOB, CAROL], &connections().await, server.srcom_state(), server.cm_state())
+        .await.unwrap()
+        .into_iter()
+        .map(|step| (step.threshold, step.qualifying))
+        .collect()
+}
+
+#[tokio::test]
+async fn points_sweep() {
+    let steps = sweep(r#"{ "type": "points", "leaderboard": "aggregated/sp", "points": 10000 }"#, ThresholdParameter::Points, "8000", "12000", 5).await;
+    assert_eq!(steps, vec![
+        ("8000".to_string(), 2),
+        ("9000".to_string(), 2),
+        ("10000".to_string(), 1),
+        ("11000".to_string(), 1),
+        ("12000".to_string(), 0)
+    ]);
+}
+
+#[tokio::test]
+async fn time_sweep() {
+    let requirement = format!(r#"{{ "type": "ranktime", "platform": "srcom", {}, "time": "01:05:00", "top": 3 }}"#, SP_NO_SLA);
+
+    let steps = sweep(&requirement, ThresholdParameter::Time, "01:00:00", "01:05:00", 3).await;
+    assert_eq!(steps, vec![
+        ("01:00:00".to_string(), 0),
+        ("01:02:30".to_string(), 2),
+        ("01:05:00".to_string(), 3)
+    ]);
+
+    let chamber_steps = sweep(r#"{ "type": "cm_chamber", "chamber": 47458, "time": "00:00:11" }"#, ThresholdParameter::Time, "00:00:10", "00:00:12.5", 2).await;
+    assert_eq!(chamber_steps, vec![("00:00:10".to_string(), 0), ("00:00:12.500".to_string(), 2)]);
+}
+
+#[tokio::test]
+async fn sweep_thresholds() {
+    let top = ThresholdSweep::new(ThresholdParameter::Top, "1", "3", 10).unwrap();
+    assert_eq!(top.thresholds(), vec!["1", "2", "3"]);
+
+    assert!(ThresholdSweep::new(ThresholdParameter::Top, "1", "3", 1).is_err());
+    assert!(ThresholdSweep::new(ThresholdParameter::Time, "soon", "later", 3).is_err());
+
+    let definition = single_requirement(r#"{ "type": "recent", "platform": "cm", "months": 6 }"#);
+    assert_eq!(ThresholdParameter::default_for(&definition.badges[0].requirements[0]), None);
+}
+
+#[tokio::test]
+async fn definition_comparison() {
+    let server = FixtureServer::start().await;
+    let old: RoleDefinition = json5::from_str(&format!(r#"{{
+        "badges": [
+            {{ "name": "Runner", "requirements": [ {{ "type": "rank", "platform": "srcom", {}, "top": 1 }} ] }},
+            {{ "name": "Veteran", "requirements": [ {{ "type": "points", "leaderboard": "aggregated/overall", "points": 20000 }} ] }}
+        ]
+    }}"#, SP_NO_SLA)).unwrap();
+    let new: RoleDefinition = json5::from_str(&format!(r#"{{
+        "badges": [
+            {{
+                "name": "Runner",
+                "requirements": [
+                    {{ "type": "rank", "platform": "srcom", {}, "top": 2 }},
+                    {{ "type": "recent", "platform": "cm", "months": 6 }}
+                ]
+            }},
+            {{ "name": "Active", "requirements": [ {{ "type": "recent", "platform": "cm", "months": 6 }} ] }}
+        ]
+    }}"#, SP_NO_SLA)).unwrap();
+    let users = vec![(ALICE, "alice".to_string()), (BOB, "bob".to_string()), (CAROL, "carol".to_string())];
+
+    let comparison = compare_definitions(&old, &new, &users, &connections().await, server.srcom_state(), server.cm_state())
+        .await.unwrap();
+
+    let badges: Vec<&str> = comparison.badges.iter().map(|badge| badge.badge.as_str()).collect();
+    assert_eq!(badges, vec!["Runner", "Veteran", "Active"]);
+
+    let [runner, veteran, active] = &comparison.badges[..] else { unreachable!() };
+    assert_eq!((&runner.gained, &runner.lost, &runner.kept), (&vec![BOB], &vec![], &vec![ALICE]));
+    assert_eq!((&veteran.gained, &veteran.lost, &veteran.kept), (&vec![], &vec![ALICE], &vec![]));
+    assert_eq!((&active.gained, &active.lost, &active.kept), (&vec![BOB], &vec![], &vec![]));
+
+    assert_eq!(String::from_utf8(comparison.to_csv().unwrap()).unwrap(), "\
+Discord User,Badge,Old,New,Change
+alice,Runner,complete,partial,keep
+alice,Veteran,complete,none,lose
+bob,Runner,none,complete,gain
+bob,Active,none,complete,gain
+");
+}
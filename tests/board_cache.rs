@@ -0,0 +1,222 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use chrono::Duration;
+use role_manager::analyzer::role_definition::RoleDefinition;
+use role_manager::analyzer::user::analyze_user;
+use role_manager::boards::cache::{BoardCache, CachedResponse, FileBoardCache, SingleFlight};
+use role_manager::boards::srcom::category::CategoryId;
+use role_manager::boards::srcom::game::GameId;
+use role_manager::boards::srcom::user::UserId;
+use role_manager::boards::srcom::variable::{VariableId, VariableValueId};
+use role_manager::error::RoleManagerError;
+use role_manager::store::StoreFuture;
+use common::{connections, FixtureServer, ALICE};
+
+/// Badges needing every kind of board, so an analysis touches each cached resource
+fn definition() -> RoleDefinition {
+    json5::from_str(r#"{ "badges": [
+        { "name": "Runner", "requirements": [
+            { "type": "rank", "platform": "srcom", "game": "om1mw4d2", "category": "jzd33ndn", "variables": [ { "variable": "9l7x7xzn", "choice": "z196dyy1" } ], "top": 1 },
+            { "type": "points", "leaderboard": "aggregated/sp", "points": 10000 },
+            { "type": "cm_chamber", "chamber": 47458, "top": 1 },
+            { "type": "recent", "platform": "cm", "months": 6 }
+        ] }
+    ] }"#).unwrap()
+}
+
+/// An empty directory for a test's cache
+fn cache_directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("role-manager-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    directory
+}
+
+/// Analyzes Alice against a server, returning how many requirements she meets (all but activity)
+async fn analyze_alice(server: &FixtureServer, cache: &Arc<dyn BoardCache>, cache_persist_time: Duration) -> usize {
+    let definition = definition();
+    let analysis = analyze_user(
+        ALICE,
+        &definition,
+        &connections().await,
+        server.srcom_state_persisting(cache_persist_time).with_cache(Arc::clone(cache)),
+        server.cm_state_persisting(cache_persist_time).with_cache(Arc::clone(cache)),
+        true
+    ).await.unwrap();
+
+    analysis.badges[0].met_requirements.len()
+}
+
+#[tokio::test]
+async fn boards_survive_restarts() {
+    let directory = cache_directory("boards-survive-restarts");
+    let cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(&directory));
+
+    let server = FixtureServer::start().await;
+    assert_eq!(analyze_alice(&server, &cache, Duration::minutes(10)).await, 3);
+    assert!(!server.requests().is_empty());
+
+    // A new state (as after a restart) is served entirely from the directory
+    let restarted_server = FixtureServer::start().await;
+    let restarted_cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(&directory));
+    assert_eq!(analyze_alice(&restarted_server, &restarted_cache, Duration::minutes(10)).await, 3);
+    assert_eq!(restarted_server.requests(), Vec::<String>::new());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn embedded_items_are_persisted() {
+    let directory = cache_directory("embedded-items-are-persisted");
+    let cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(&directory));
+
+    let server = FixtureServer::start().await;
+    analyze_alice(&server, &cache, Duration::minutes(10)).await;
+
+    // The game and variable were only ever embedded in the leaderboard
+    assert!(!server.requests().iter().any(|target| target.starts_with("/srcom/games/")));
+    let restarted_server = FixtureServer::start().await;
+    let srcom_state = restarted_server.srcom_state().with_cache(Arc::clone(&cache));
+    let game = srcom_state.fetch_game(GameId("om1mw4d2".to_string())).await.unwrap();
+    assert_eq!(game.names.international, "Portal 2");
+    assert_eq!(restarted_server.requests(), Vec::<String>::new());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn stale_boards_are_served_while_refreshing() {
+    let directory = cache_directory("stale-boards-are-served-while-refreshing");
+    let cache: Arc<dyn BoardCache> = Arc::new(FileBoardCache::new(&directory));
+
+    let server = FixtureServer::start().await;
+    analyze_alice(&server, &cache, Duration::minutes(10)).await;
+
+    // Everything is stale straight away, but the analysis still succeeds from the cached copies
+    let restarted_server = FixtureServer::start().await;
+    assert_eq!(analyze_alice(&restarted_server, &cache, Duration::zero()).await, 3);
+
+    let mut refreshed = false;
+    for _ in 0..100 {
+        if restarted_server.requests().iter().any(|target| target.starts_with("/srcom/leaderboards/")) {
+            refreshed = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(refreshed, "stale leaderboard was never refreshed");
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn damaged_entries_are_ignored() {
+    let directory = cache_directory("damaged-entries-are-ignored");
+    let cache = FileBoardCache::new(&directory);
+
+    let response = CachedResponse {
+        body: r#"{"data": {}}"#.to_string(),
+        fetched_at: chrono::Utc::now().naive_utc()
+    };
+    cache.save("srcom/games/om1mw4d2", response.clone()).await.unwrap();
+    assert_eq!(cache.load("srcom/games/om1mw4d2").await.unwrap().unwrap().body, response.body);
+
+    std::fs::write(directory.join("srcom_games_om1mw4d2.json"), "not json").unwrap();
+    assert!(cache.load("srcom/games/om1mw4d2").await.unwrap().is_none());
+    assert!(cache.load("srcom/games/missing0").await.unwrap().is_none());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+fn sp_variables() -> BTreeMap<VariableId, VariableValueId> {
+    BTreeMap::from([(VariableId("9l7x7xzn".to_string()), VariableValueId("z196dyy1".to_string()))])
+}
+
+#[tokio::test]
+async fn concurrent_fetches_share_one_download() {
+    let server = FixtureServer::start().await;
+    server.delay("/srcom/leaderboards/", std::time::Duration::from_millis(300));
+    let srcom_state = server.srcom_state();
+
+    let fetch = || srcom_state.fetch_leaderboard_with_variables(GameId("om1mw4d2".to_string()), CategoryId("jzd33ndn".to_string()), sp_variables());
+    let (first, second, third) = tokio::join!(fetch(), fetch(), fetch());
+    assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+    assert!(third.is_ok());
+
+    let leaderboard_requests = server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/leaderboards/"))
+        .count();
+    assert_eq!(leaderboard_requests, 1);
+}
+
+#[tokio::test]
+async fn slow_boards_dont_block_other_fetches() {
+    let server = FixtureServer::start().await;
+    server.delay("/srcom/leaderboards/", std::time::Duration::from_millis(1000));
+    let srcom_state = server.srcom_state();
+
+    let started = Instant::now();
+    let sp = srcom_state.fetch_leaderboard_with_variables(GameId("om1mw4d2".to_string()), CategoryId("jzd33ndn".to_string()), sp_variables());
+    let coop = srcom_state.fetch_leaderboard(GameId("om1mw4d2".to_string()), CategoryId("l9kv40kg".to_string()));
+    let user = async {
+        // Looked up while both boards are still downloading
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let user = srcom_state.fetch_user(UserId::try_from("user0001").unwrap()).await;
+        (user, started.elapsed())
+    };
+
+    let (sp, coop, (user, user_elapsed)) = tokio::join!(sp, coop, user);
+    assert!(sp.is_ok() && coop.is_ok());
+    assert_eq!(user.unwrap().names.international, "Alice");
+    assert!(user_elapsed < std::time::Duration::from_millis(800), "user waited {:?} for the boards", user_elapsed);
+
+    // Both boards downloaded side by side rather than one after the other
+    assert!(started.elapsed() < std::time::Duration::from_millis(1800), "boards took {:?}", started.elapsed());
+}
+
+/// Cache on a disk which is full, so nothing can be saved
+#[derive(Debug)]
+struct UnwritableCache;
+
+impl BoardCache for UnwritableCache {
+    fn load<'a>(&'a self, _key: &'a str) -> StoreFuture<'a, Option<CachedResponse>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn save<'a>(&'a self, _key: &'a str, _response: CachedResponse) -> StoreFuture<'a, ()> {
+        Box::pin(async { Err(RoleManagerError::new("No space left on device".to_string())) })
+    }
+}
+
+#[tokio::test]
+async fn failed_saves_dont_fail_the_analysis() {
+    let cache: Arc<dyn BoardCache> = Arc::new(UnwritableCache);
+
+    let server = FixtureServer::start().await;
+    assert_eq!(analyze_alice(&server, &cache, Duration::minutes(10)).await, 3);
+}
+
+#[tokio::test]
+async fn single_flight_forgets_finished_keys() {
+    let single_flight = SingleFlight::default();
+
+    let first = single_flight.lock("srcom/games/om1mw4d2").await;
+    let waiting = {
+        let single_flight = single_flight.clone();
+        tokio::spawn(async move {
+            let _second = single_flight.lock("srcom/games/om1mw4d2").await;
+        })
+    };
+    tokio::task::yield_now().await;
+    assert_eq!(single_flight.key_count(), 1);
+
+    // The waiting fetch still needs the key after the first one ends
+    drop(first);
+    waiting.await.unwrap();
+
+    assert_eq!(single_flight.key_count(), 0);
+}
@@ -0,0 +1,230 @@
+//! A small HTTP server which answers speedrun.com and board.portal2.sr requests from the fixtures in
+//! `tests/fixtures`, so the analyzer can be tested without network access.
+
+// Each test crate only uses some of these helpers
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use chrono::{Duration, Utc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use role_manager::boards::cm::CmBoardsState;
+use role_manager::boards::srcom::SrComBoardsState;
+use role_manager::model::lumadb::verified_connections;
+use role_manager::store::ConnectionStore;
+use role_manager::store::memory::MemoryStore;
+
+/// Request targets (with the `embed` parameter removed) and the fixture served for each of them
+const ROUTES: &[(&str, &str)] = &[
+    ("/srcom/games/om1mw4d2", "srcom/game.json"),
+    ("/srcom/categories/jzd33ndn", "srcom/category_sp.json"),
+    ("/srcom/categories/l9kv40kg", "srcom/category_coop.json"),
+    ("/srcom/categories/ilcat001", "srcom/category_il.json"),
+    ("/srcom/levels/lvl00001", "srcom/level.json"),
+    ("/srcom/variables/9l7x7xzn", "srcom/variable.json"),
+    ("/srcom/users/user0001", "srcom/user.json"),
+    ("/srcom/leaderboards/om1mw4d2/category/jzd33ndn?var-9l7x7xzn=z196dyy1", "srcom/leaderboard_sp.json"),
+    ("/srcom/leaderboards/om1mw4d2/category/jzd33ndn?timing=realtime&var-9l7x7xzn=z196dyy1", "srcom/leaderboard_sp.json"),
+    ("/srcom/leaderboards/om1mw4d2/category/jzd33ndn?emulators=false&platform=8gej2n93&var-9l7x7xzn=z196dyy1", "srcom/leaderboard_sp_pc.json"),
+    ("/srcom/leaderboards/om1mw4d2/category/l9kv40kg", "srcom/leaderboard_coop.json"),
+    ("/srcom/leaderboards/om1mw4d2/level/lvl00001/ilcat001", "srcom/leaderboard_il.json"),
+    ("/srcom/runs?category=jzd33ndn&direction=desc&game=om1mw4d2&max=200&orderby=date&status=verified&user=user0001", "srcom/runs_user0001.json"),
+    ("/srcom/runs?category=jzd33ndn&direction=desc&game=om1mw4d2&max=200&orderby=date&status=verified&user=user0002", "srcom/runs_user0002.json"),
+    ("/srcom/runs?category=jzd33ndn&direction=desc&game=om1mw4d2&max=200&orderby=date&status=verified&user=user0003", "srcom/runs_user0003.json"),
+    ("/srcom/runs?category=jzd33ndn&game=om1mw4d2&max=200", "srcom/runs_sp_page1.json"),
+    ("/srcom/runs?category=jzd33ndn&game=om1mw4d2&max=200&offset=200", "srcom/runs_sp_page2.json"),
+    ("/cm/aggregated/sp/json", "cm/aggregated_sp.json"),
+    ("/cm/aggregated/overall/json", "cm/aggregated_overall.json"),
+    ("/cm/chamber/47458/json", "cm/chamber.json"),
+    ("/cm/api-v2/active-profiles", "cm/active_profiles.json"),
+    ("/cm/profile/76561198000000001/json", "cm/profile.json"),
+];
+
+pub const ALICE: u64 = 1;
+pub const BOB: u64 = 2;
+pub const CAROL: u64 = 3;
+
+pub struct FixtureServer {
+    base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    delays: Arc<Mutex<Vec<(String, std::time::Duration)>>>,
+    failures: Arc<Mutex<Vec<Failure>>>
+}
+
+/// Responses to request targets starting with `prefix` which fail before the fixture is served
+struct Failure {
+    prefix: String,
+    status: &'static str,
+    remaining: usize
+}
+
+impl FixtureServer {
+    pub async fn start() -> FixtureServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let failures = Arc::new(Mutex::new(Vec::new()));
+
+        let server_base_url = base_url.clone();
+        let server_requests = Arc::clone(&requests);
+        let server_delays = Arc::clone(&delays);
+        let server_failures = Arc::clone(&failures);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_base_url.clone(), Arc::clone(&server_requests), Arc::clone(&server_delays), Arc::clone(&server_failures)));
+            }
+        });
+
+        FixtureServer {
+            base_url,
+            requests,
+            delays,
+            failures
+        }
+    }
+
+    /// Holds back responses to request targets starting with `prefix`, like a slow download
+    pub fn delay(&self, prefix: &str, delay: std::time::Duration) {
+        self.delays.lock().unwrap().push((prefix.to_string(), delay));
+    }
+
+    /// Answers the next `times` requests to targets starting with `prefix` with `status` (e.g. `420 Enhance Your Calm`)
+    pub fn fail(&self, prefix: &str, status: &'static str, times: usize) {
+        self.failures.lock().unwrap().push(Failure { prefix: prefix.to_string(), status, remaining: times });
+    }
+
+    pub fn srcom_state(&self) -> SrComBoardsState {
+        self.srcom_state_persisting(Duration::minutes(10))
+    }
+
+    pub fn cm_state(&self) -> CmBoardsState {
+        self.cm_state_persisting(Duration::minutes(10))
+    }
+
+    /// A state whose cached boards go stale after `cache_persist_time`
+    pub fn srcom_state_persisting(&self, cache_persist_time: Duration) -> SrComBoardsState {
+        SrComBoardsState::with_base_url(cache_persist_time, format!("{}/srcom", self.base_url))
+    }
+
+    /// A state whose cached boards go stale after `cache_persist_time`
+    pub fn cm_state_persisting(&self, cache_persist_time: Duration) -> CmBoardsState {
+        CmBoardsState::with_base_url(cache_persist_time, format!("{}/cm", self.base_url))
+    }
+
+    /// Every request target served so far, in the form used by the route table
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Alice and Bob have linked both speedrun.com and steam, Carol only speedrun.com
+pub fn store() -> MemoryStore {
+    let store = MemoryStore::new();
+    store.add_connection(ALICE, "srcom", "user0001");
+    store.add_connection(ALICE, "steam", "76561198000000001");
+    store.add_connection(BOB, "srcom", "user0002");
+    store.add_connection(BOB, "steam", "76561198000000002");
+    store.add_connection(CAROL, "srcom", "user0003");
+
+    store
+}
+
+pub async fn connections() -> Vec<verified_connections::Model> {
+    store().connections().await.unwrap()
+}
+
+async fn handle_connection(mut stream: TcpStream, base_url: String, requests: Arc<Mutex<Vec<String>>>, delays: Arc<Mutex<Vec<(String, std::time::Duration)>>>, failures: Arc<Mutex<Vec<Failure>>>) {
+    let mut buffer = Vec::new();
+    let header_end = loop {
+        let mut chunk = [0u8; 4096];
+        let read = match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read
+        };
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+    };
+
+    // Drain the body so the client never sees the connection reset mid-request
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body_read = buffer.len() - header_end;
+    while body_read < content_length {
+        let mut chunk = [0u8; 4096];
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => body_read += read
+        }
+    }
+
+    let target = normalize_target(head.split_whitespace().nth(1).unwrap_or("/"));
+    requests.lock().unwrap().push(target.clone());
+
+    let delay = delays.lock().unwrap().iter()
+        .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+        .map(|(_, delay)| *delay);
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let failure = failures.lock().unwrap().iter_mut()
+        .find(|failure| failure.remaining > 0 && target.starts_with(failure.prefix.as_str()))
+        .map(|failure| {
+            failure.remaining -= 1;
+            failure.status
+        });
+
+    // Failures ask to be retried straight away, so tests don't wait out the backoff
+    let (status, body, retry_after) = match (failure, ROUTES.iter().find(|(route, _)| *route == target)) {
+        (Some(status), _) => (status, format!(r#"{{"status": {}, "message": "Failing on purpose"}}"#, &status[..3]), "Retry-After: 0\r\n"),
+        (None, Some((_, fixture))) => ("200 OK", load_fixture(fixture, &base_url), ""),
+        (None, None) => ("404 Not Found", format!(r#"{{"status": 404, "message": "No fixture for {}"}}"#, target), "")
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, retry_after, body.len(), body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Drops the `embed` parameter and sorts the remaining ones, so the route table doesn't depend on query order
+fn normalize_target(target: &str) -> String {
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => return target.to_string()
+    };
+
+    let mut params: Vec<&str> = query.split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("embed="))
+        .collect();
+    params.sort();
+
+    if params.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, params.join("&"))
+    }
+}
+
+/// Fixtures may use `$RECENT_DATE` for runs which have to stay within recent requirements, and `$BASE_URL` for
+/// links back to this server
+fn load_fixture(name: &str, base_url: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let recent_date = (Utc::now() - Duration::days(10)).date_naive().to_string();
+
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Failed to read fixture {}: {}", path.display(), err))
+        .replace("$RECENT_DATE", &recent_date)
+        .replace("$BASE_URL", base_url)
+}
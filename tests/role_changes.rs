@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use role_manager::analyzer::role_definition::RoleDefinition;
+use role_manager::analyzer::user::{AnalyzedUser, AnalyzedUserBadge, MetRequirement, MetRequirementCause, RequirementGap, UnmetRequirement};
+use role_manager::model::lumadb::manual_role_assignments;
+use role_manager::roles::{MemberRoles, RoleChangeKind};
+use role_manager::roles::executor::{execute_plan, AuditLogExecutor, DryRunExecutor};
+use role_manager::roles::planner::plan_role_changes;
+use role_manager::server::ServerConfig;
+use role_manager::store::ChangeLogStore;
+use role_manager::store::memory::MemoryStore;
+
+const ACTIVE_ROLE: u64 = 10;
+const ACTIVE_COMPLETE_ROLE: u64 = 11;
+const VETERAN_ROLE: u64 = 20;
+
+fn definition() -> RoleDefinition {
+    json5::from_str(r#"{
+        "badges": [
+            {
+                "name": "Active",
+                "requirements": [
+                    { "type": "recent", "platform": "cm", "months": 6 },
+                    { "type": "points", "leaderboard": "aggregated/sp", "points": 5000 }
+                ]
+            },
+            {
+                "name": "Veteran",
+                "requirements": [
+                    { "type": "manual" },
+                    { "type": "points", "leaderboard": "aggregated/overall", "points": 20000 }
+                ]
+            }
+        ]
+    }"#).unwrap()
+}
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        dry_run: false,
+        badge_roles: HashMap::from([("Active".to_string(), ACTIVE_ROLE), ("Veteran".to_string(), VETERAN_ROLE)]),
+        completed_badge_roles: HashMap::from([("Active".to_string(), ACTIVE_COMPLETE_ROLE)]),
+        ..Default::default()
+    }
+}
+
+fn member(user_id: u64, roles: Vec<u64>) -> MemberRoles {
+    MemberRoles {
+        user_id,
+        name: format!("user{}", user_id),
+        roles
+    }
+}
+
+/// An analysis where the user meets the first `met` requirements of the first badge
+fn active_analysis(definition: &RoleDefinition, discord_id: u64, met: usize) -> AnalyzedUser<'_> {
+    let badge = &definition.badges[0];
+
+    AnalyzedUser {
+        discord_id,
+        external_accounts: vec![],
+        badges: vec![AnalyzedUserBadge {
+            definition: badge,
+            met_requirements: badge.requirements.iter().take(met)
+                .map(|definition| MetRequirement {
+                    definition,
+                    cause: MetRequirementCause::CmActivity { steam_id: 1 },
+                    branches: vec![]
+                })
+                .collect(),
+            unmet_requirements: vec![]
+        }],
+        unearned_badges: vec![]
+    }
+}
+
+#[test]
+fn adds_base_and_completed_roles() {
+    let definition = definition();
+    let members = vec![member(1, vec![]), member(2, vec![ACTIVE_ROLE])];
+    let analyses = vec![active_analysis(&definition, 1, 2), active_analysis(&definition, 2, 1)];
+
+    let plan = plan_role_changes(&definition, &config(), &members, &analyses, &[]);
+
+    assert_eq!(plan.len(), 2);
+    assert!(plan.iter().all(|change| change.user_id == 1 && change.kind == RoleChangeKind::Add));
+    assert_eq!(plan[0].role_id, ACTIVE_ROLE);
+    assert_eq!(plan[1].role_id, ACTIVE_COMPLETE_ROLE);
+    assert!(plan[1].completed);
+}
+
+#[test]
+fn removes_roles_no_longer_met() {
+    let definition = definition();
+    let members = vec![member(1, vec![ACTIVE_ROLE, ACTIVE_COMPLETE_ROLE])];
+    let analyses = vec![active_analysis(&definition, 1, 1)];
+
+    let plan = plan_role_changes(&definition, &config(), &members, &analyses, &[]);
+
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].role_id, ACTIVE_COMPLETE_ROLE);
+    assert_eq!(plan[0].kind, RoleChangeKind::Remove);
+}
+
+#[test]
+fn keeps_manual_roles() {
+    let definition = definition();
+    let members = vec![member(1, vec![ACTIVE_ROLE, VETERAN_ROLE])];
+    let manual_assignments = vec![manual_role_assignments::Model {
+        user_id: 1,
+        server_id: 0,
+        role_id: ACTIVE_ROLE as i64
+    }];
+
+    // Active was assigned by hand, and Veteran has a manual requirement so is never removed
+    let plan = plan_role_changes(&definition, &config(), &members, &[], &manual_assignments);
+
+    assert!(plan.is_empty());
+}
+
+#[tokio::test]
+async fn removals_explain_themselves_in_the_change_log() {
+    let definition = definition();
+    let badge = &definition.badges[0];
+    let members = vec![member(1, vec![ACTIVE_ROLE])];
+    let analyses = vec![AnalyzedUser {
+        discord_id: 1,
+        external_accounts: vec![],
+        badges: vec![],
+        unearned_badges: vec![AnalyzedUserBadge {
+            definition: badge,
+            met_requirements: vec![],
+            unmet_requirements: vec![
+                UnmetRequirement {
+                    definition: &badge.requirements[0],
+                    gap: Some(RequirementGap::Inactive { months: 6 }),
+                    branches: vec![]
+                },
+                UnmetRequirement {
+                    definition: &badge.requirements[1],
+                    gap: None,
+                    branches: vec![]
+                }
+            ]
+        }]
+    }];
+
+    let plan = plan_role_changes(&definition, &config(), &members, &analyses, &[]);
+
+    let store = MemoryStore::new();
+    let executor = AuditLogExecutor {
+        inner: DryRunExecutor,
+        log: &store,
+        definition_version: None
+    };
+    execute_plan(0, &plan, &executor).await.unwrap();
+
+    let history = store.change_history_page(0, 1, 10, 0).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].direction, "remove");
+    assert_eq!(history[0].reason, "No longer meets any requirement");
+    assert_eq!(history[0].causes, r#"["CM Recent: No activity in the last 6 months","SP 5000p: Not met"]"#);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::time::Duration;
+use role_manager::boards::retry::RetryConfig;
+use role_manager::boards::srcom::game::GameId;
+use role_manager::boards::srcom::SrComBoardsState;
+use common::FixtureServer;
+
+const GAME: &str = "/srcom/games/om1mw4d2";
+
+/// Retries without waiting, so failures are reported quickly
+fn impatient(max_retries: u32, failure_threshold: u32) -> RetryConfig {
+    RetryConfig {
+        max_retries,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+        failure_threshold,
+        cooldown: Duration::from_secs(60)
+    }
+}
+
+fn game_requests(server: &FixtureServer) -> usize {
+    server.requests().iter()
+        .filter(|target| target.as_str() == GAME)
+        .count()
+}
+
+async fn fetch_portal2(srcom: &SrComBoardsState) -> Result<String, String> {
+    srcom.fetch_game(GameId("om1mw4d2".to_string())).await
+        .map(|game| game.id.0.clone())
+        .map_err(|err| err.cause)
+}
+
+#[tokio::test]
+async fn throttled_requests_are_retried() {
+    let server = FixtureServer::start().await;
+    server.fail(GAME, "420 Enhance Your Calm", 1);
+    server.fail(GAME, "503 Service Unavailable", 1);
+
+    let srcom = server.srcom_state().with_retry(impatient(4, 5));
+
+    assert_eq!(fetch_portal2(&srcom).await, Ok("om1mw4d2".to_string()));
+    assert_eq!(game_requests(&server), 3);
+}
+
+#[tokio::test]
+async fn failures_report_the_response_body() {
+    let server = FixtureServer::start().await;
+    server.fail(GAME, "429 Too Many Requests", 10);
+
+    let srcom = server.srcom_state().with_retry(impatient(2, 5));
+
+    let cause = fetch_portal2(&srcom).await.unwrap_err();
+    assert!(cause.contains("429") && cause.contains("Failing on purpose"), "{}", cause);
+    assert_eq!(game_requests(&server), 3);
+
+    // Resources which don't exist are reported right away
+    let cause = srcom.fetch_game(GameId("missing0".to_string())).await.unwrap_err().cause;
+    assert!(cause.contains("404") && cause.contains("No fixture for /srcom/games/missing0"), "{}", cause);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn repeated_failures_open_the_circuit() {
+    let server = FixtureServer::start().await;
+    server.fail(GAME, "500 Internal Server Error", 10);
+
+    let srcom = server.srcom_state().with_retry(impatient(0, 2));
+
+    assert!(fetch_portal2(&srcom).await.is_err());
+    assert!(fetch_portal2(&srcom).await.is_err());
+    let cause = fetch_portal2(&srcom).await.unwrap_err();
+
+    assert!(cause.starts_with("Not contacting speedrun.com"), "{}", cause);
+    assert_eq!(game_requests(&server), 2);
+}
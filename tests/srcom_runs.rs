@@ -0,0 +1,74 @@
+mod common;
+
+use futures::{StreamExt, TryStreamExt};
+use role_manager::boards::srcom::BoardDefinition;
+use role_manager::boards::srcom::category::CategoryId;
+use role_manager::boards::srcom::game::GameId;
+use role_manager::boards::srcom::leaderboard::BoardFilters;
+use role_manager::boards::srcom::run::{Run, RunsQuery};
+use role_manager::boards::srcom::user::UserId;
+use common::FixtureServer;
+
+fn sp_runs() -> RunsQuery {
+    RunsQuery::for_category(GameId("om1mw4d2".to_string()), CategoryId("jzd33ndn".to_string()))
+}
+
+fn run_requests(server: &FixtureServer) -> usize {
+    server.requests().iter()
+        .filter(|target| target.starts_with("/srcom/runs"))
+        .count()
+}
+
+#[tokio::test]
+async fn runs_follow_pagination_links() {
+    let server = FixtureServer::start().await;
+
+    let runs: Vec<Run> = server.srcom_state().runs(sp_runs()).try_collect().await.unwrap();
+
+    // The obsolete run on the second page never shows up on the leaderboard
+    let ids: Vec<&str> = runs.iter().map(|run| run.id.0.as_str()).collect();
+    assert_eq!(ids, vec!["run00001", "run00002", "run00003", "run00004", "run00006"]);
+    assert_eq!(run_requests(&server), 2);
+}
+
+#[tokio::test]
+async fn pages_are_only_fetched_when_read() {
+    let server = FixtureServer::start().await;
+
+    let first: Vec<Run> = server.srcom_state().runs(sp_runs()).take(2).try_collect().await.unwrap();
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(run_requests(&server), 1);
+}
+
+#[tokio::test]
+async fn failed_pages_end_the_stream_with_an_error() {
+    let server = FixtureServer::start().await;
+
+    let runs: Result<Vec<Run>, _> = server.srcom_state().runs(RunsQuery::for_game(GameId("missing0".to_string()))).try_collect().await;
+
+    assert!(runs.is_err());
+}
+
+#[tokio::test]
+async fn latest_runs_respect_board_filters() {
+    let server = FixtureServer::start().await;
+    let latest_id = |filters: BoardFilters| {
+        let srcom_state = server.srcom_state();
+        async move {
+            let board = BoardDefinition {
+                game: GameId("om1mw4d2".to_string()),
+                category: CategoryId("jzd33ndn".to_string()),
+                level: None,
+                variables: Default::default(),
+                filters
+            };
+            srcom_state.fetch_latest_user_run(UserId::try_from("user0001").unwrap(), board).await.unwrap()
+                .map(|run| run.id.0.clone())
+        }
+    };
+
+    assert_eq!(latest_id(BoardFilters::default()).await.as_deref(), Some("run00006"));
+    assert_eq!(latest_id(BoardFilters { date: Some("2020-06-01".to_string()), ..Default::default() }).await.as_deref(), Some("run00001"));
+    assert_eq!(latest_id(BoardFilters { emulators: Some(true), ..Default::default() }).await, None);
+}
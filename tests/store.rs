@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use role_manager::error::RoleManagerError;
+use role_manager::model::lumadb::role_change_log;
+use role_manager::roles::{RoleChange, RoleChangeKind};
+use role_manager::roles::executor::{execute_plan, AuditLogExecutor, DryRunExecutor, RoleExecutor};
+use role_manager::store::{AssignmentStore, ChangeLogStore, ConnectionStore, StoreFuture};
+use role_manager::store::memory::MemoryStore;
+
+const SERVER: u64 = 100;
+const OTHER_SERVER: u64 = 200;
+
+fn seeded_store() -> MemoryStore {
+    MemoryStore::from_seed(json5::from_str(r#"{
+        "connections": [
+            { "user_id": 1, "type": "srcom", "id": "user0001" },
+            { "user_id": 1, "type": "steam", "id": "76561198000000001" },
+            { "user_id": 2, "type": "steam", "id": "76561198000000002" }
+        ],
+        "manual_assignments": [
+            { "user_id": 1, "server_id": 100, "role_id": 10 },
+            { "user_id": 2, "server_id": 200, "role_id": 20 }
+        ]
+    }"#).unwrap())
+}
+
+fn change(user_id: u64, badge: &str, kind: RoleChangeKind) -> RoleChange {
+    RoleChange {
+        user_id,
+        user_name: format!("User {}", user_id),
+        role_id: 10,
+        badge: badge.to_string(),
+        completed: false,
+        kind,
+        reason: "Manual".to_string(),
+        causes: vec!["Manual: Assigned".to_string()]
+    }
+}
+
+#[tokio::test]
+async fn seeded_connections_and_assignments() {
+    let store = seeded_store();
+
+    assert_eq!(store.connections().await.unwrap().len(), 3);
+
+    let user_connections = store.user_connections(1).await.unwrap();
+    let types: Vec<&str> = user_connections.iter().map(|c| c.connection_type.as_str()).collect();
+    assert_eq!(types, vec!["srcom", "steam"]);
+
+    let assignments = store.manual_assignments(SERVER).await.unwrap();
+    assert_eq!(assignments.len(), 1);
+    assert_eq!(assignments[0].role_id, 10);
+}
+
+#[tokio::test]
+async fn change_history_is_paged_most_recent_first() {
+    let store = MemoryStore::new();
+    for i in 0..5 {
+        store.record_change(SERVER, &change(1, &format!("Badge {}", i), RoleChangeKind::Add), None).await.unwrap();
+    }
+    store.record_change(OTHER_SERVER, &change(1, "Elsewhere", RoleChangeKind::Add), None).await.unwrap();
+
+    assert_eq!(store.change_history_pages(SERVER, 1, 2).await.unwrap(), 3);
+    assert_eq!(store.change_history_pages(SERVER, 2, 2).await.unwrap(), 0);
+
+    let first_page = store.change_history_page(SERVER, 1, 2, 0).await.unwrap();
+    let badges: Vec<&str> = first_page.iter().map(|entry| entry.badge.as_str()).collect();
+    assert_eq!(badges, vec!["Badge 4", "Badge 3"]);
+
+    let last_page = store.change_history_page(SERVER, 1, 2, 2).await.unwrap();
+    assert_eq!(last_page.len(), 1);
+    assert_eq!(last_page[0].badge, "Badge 0");
+}
+
+#[tokio::test]
+async fn audit_log_executor_records_applied_changes() {
+    let store = MemoryStore::new();
+    let executor = AuditLogExecutor {
+        inner: DryRunExecutor,
+        log: &store,
+        definition_version: Some("abc123".to_string())
+    };
+
+    let plan = vec![change(1, "Active", RoleChangeKind::Add), change(1, "Veteran", RoleChangeKind::Remove)];
+    execute_plan(SERVER, &plan, &executor).await.unwrap();
+
+    let history = store.change_history_page(SERVER, 1, 10, 0).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].badge, "Veteran");
+    assert_eq!(history[0].direction, "remove");
+    assert_eq!(history[1].direction, "add");
+    assert_eq!(history[1].causes, r#"["Manual: Assigned"]"#);
+    assert_eq!(history[1].definition_version.as_deref(), Some("abc123"));
+}
+
+/// Change log which is always unavailable
+struct FailingLog;
+
+impl ChangeLogStore for FailingLog {
+    fn record_change<'a>(&'a self, _server_id: u64, _change: &'a RoleChange, _definition_version: Option<String>) -> StoreFuture<'a, ()> {
+        Box::pin(async { Err(RoleManagerError::new("change log unavailable".to_string())) })
+    }
+
+    fn change_history_pages(&self, _server_id: u64, _user_id: u64, _page_size: u64) -> StoreFuture<'_, u64> {
+        Box::pin(async { Ok(0) })
+    }
+
+    fn change_history_page(&self, _server_id: u64, _user_id: u64, _page_size: u64, _page: u64) -> StoreFuture<'_, Vec<role_change_log::Model>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+}
+
+/// Counts the changes it is asked to apply
+#[derive(Default)]
+struct CountingExecutor {
+    applied: AtomicUsize
+}
+
+impl RoleExecutor for CountingExecutor {
+    async fn apply(&self, _guild_id: u64, _change: &RoleChange) -> Result<(), RoleManagerError> {
+        self.applied.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn failing_change_log_does_not_stop_the_plan() {
+    let executor = AuditLogExecutor {
+        inner: CountingExecutor::default(),
+        log: &FailingLog,
+        definition_version: None
+    };
+
+    let plan = vec![change(1, "Active", RoleChangeKind::Add), change(2, "Veteran", RoleChangeKind::Remove)];
+    execute_plan(SERVER, &plan, &executor).await.unwrap();
+
+    assert_eq!(executor.inner.applied.load(Ordering::SeqCst), 2);
+}